@@ -0,0 +1,725 @@
+// Assembler for Octo source (https://github.com/JohnEarnest/Octo), the syntax most CHIP-8
+// homebrew is written in. Covers labels, :const, :alias, :org, :byte, :call, every instruction,
+// if/then, if/begin/else/end, loop/again/while and bare numbers as data (sprites).
+// Execution starts at the main label, like in Octo. Macros and :calc aren't supported.
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use crate::CHIP8_START_OF_PROGRAM;
+use crate::instruction::Instruction;
+
+// Where in the source assembly failed, lines start at 1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AssembleError {}
+
+// Assemble a whole source file into a ROM that loads at 0x200
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    let mut assembler = Assembler::new(source);
+    while !assembler.at_end() {
+        assembler.statement()?;
+    }
+    assembler.finish()
+}
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+#[derive(Clone, Copy)]
+enum FixupKind {
+    // the low 12 bits of the opcode at the offset
+    Address,
+    // the whole word at the offset (i := long)
+    Long,
+}
+
+// A reference to a label that wasn't defined yet when it was used
+struct Fixup {
+    offset: usize,
+    kind: FixupKind,
+    name: String,
+    line: usize,
+}
+
+enum Block {
+    // offset of the jump over the body (or the else part) that end patches
+    If { jump: usize, has_else: bool, line: usize },
+    // offsets of the jumps out of the loop that again patches
+    Loop { start: u16, breaks: Vec<usize>, line: usize },
+}
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Register(u8),
+    Byte(u8),
+}
+
+#[derive(Clone, Copy)]
+enum Test {
+    Equal(Operand),
+    NotEqual(Operand),
+    Key,
+    NotKey,
+}
+
+#[derive(Clone, Copy)]
+struct Condition {
+    x: u8,
+    test: Test,
+}
+
+impl Condition {
+    // The instruction that skips the next one when the condition is (or isn't) met
+    fn skip_when(self, met: bool) -> Instruction {
+        let x = self.x;
+        match (self.test, met) {
+            (Test::Equal(Operand::Byte(nn)), true) | (Test::NotEqual(Operand::Byte(nn)), false) => Instruction::SkipIfEqual(x, nn),
+            (Test::Equal(Operand::Byte(nn)), false) | (Test::NotEqual(Operand::Byte(nn)), true) => Instruction::SkipIfNotEqual(x, nn),
+            (Test::Equal(Operand::Register(y)), true) | (Test::NotEqual(Operand::Register(y)), false) => Instruction::SkipIfRegistersEqual(x, y),
+            (Test::Equal(Operand::Register(y)), false) | (Test::NotEqual(Operand::Register(y)), true) => Instruction::SkipIfRegistersNotEqual(x, y),
+            (Test::Key, true) | (Test::NotKey, false) => Instruction::SkipIfKey(x),
+            (Test::Key, false) | (Test::NotKey, true) => Instruction::SkipIfNotKey(x),
+        }
+    }
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    // the ROM, rom[0] lives at 0x200
+    rom: Vec<u8>,
+    address: usize,
+    labels: HashMap<&'a str, u16>,
+    constants: HashMap<&'a str, i64>,
+    aliases: HashMap<&'a str, u8>,
+    fixups: Vec<Fixup>,
+    blocks: Vec<Block>,
+    // whether 0x200 holds the jump to main, which finish fills in
+    entry_jump: bool,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(source: &'a str) -> Self {
+        // tokens are separated by whitespace, # starts a comment
+        let tokens = source.lines()
+            .enumerate()
+            .flat_map(|(index, line)| {
+                let code = line.split('#').next().unwrap_or("");
+                code.split_whitespace().map(move |text| Token { text, line: index + 1 })
+            })
+            .collect();
+
+        // programs start at main, 0x200 jumps there unless main turns out to come first
+        Self {
+            tokens,
+            position: 0,
+            rom: vec![0x10, 0x00],
+            address: CHIP8_START_OF_PROGRAM + 2,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+            entry_jump: true,
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    // Line of the token just read, for error messages
+    fn line(&self) -> usize {
+        match self.position.checked_sub(1).and_then(|index| self.tokens.get(index)) {
+            Some(token) => token.line,
+            None => self.tokens.first().map_or(1, |token| token.line),
+        }
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, AssembleError> {
+        Err(AssembleError { line: self.line(), message })
+    }
+
+    fn next(&mut self) -> Result<&'a str, AssembleError> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.text)
+            },
+            None => self.error(String::from("unexpected end of file")),
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).map(|token| token.text)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        if token != expected {
+            return self.error(format!("expected '{}', found '{}'", expected, token));
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), AssembleError> {
+        let token = self.next()?;
+
+        match token {
+            ":" => {
+                let name = self.name()?;
+                if name == "main" && self.entry_jump && self.rom.len() == 2 && self.address == CHIP8_START_OF_PROGRAM + 2 {
+                    // nothing before main, so no need to jump to it
+                    self.rom.clear();
+                    self.address = CHIP8_START_OF_PROGRAM;
+                    self.entry_jump = false;
+                    for address in self.labels.values_mut().filter(|address| **address == CHIP8_START_OF_PROGRAM as u16 + 2) {
+                        *address = CHIP8_START_OF_PROGRAM as u16;
+                    }
+                }
+                if self.labels.insert(name, self.address as u16).is_some() {
+                    return self.error(format!("label '{}' is defined twice", name));
+                }
+            },
+            ":const" => {
+                let name = self.name()?;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+            },
+            ":alias" => {
+                let name = self.name()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            },
+            ":org" => {
+                let address = self.value()?;
+                if address < CHIP8_START_OF_PROGRAM as i64 || address > 0xFFFF {
+                    return self.error(format!(":org address {:X} is outside the program", address));
+                }
+                self.address = address as usize;
+            },
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit_byte(byte)?;
+            },
+            ":call" => {
+                let target = self.next()?;
+                self.emit_with_target(Instruction::Call(0), target)?;
+            },
+            ":breakpoint" | ":monitor" => {
+                // debugger hints for Octo, they don't produce any code
+                self.next()?;
+                if token == ":monitor" {
+                    self.next()?;
+                }
+            },
+            ";" | "return" => self.emit(Instruction::Return)?,
+            "clear" => self.emit(Instruction::Clear)?,
+            "hires" => self.emit(Instruction::HighRes)?,
+            "lores" => self.emit(Instruction::LowRes)?,
+            "exit" => self.emit(Instruction::Exit)?,
+            "scroll-left" => self.emit(Instruction::ScrollLeft)?,
+            "scroll-right" => self.emit(Instruction::ScrollRight)?,
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(Instruction::ScrollDown(n))?;
+            },
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit(Instruction::ScrollUp(n))?;
+            },
+            "jump" => {
+                let target = self.next()?;
+                self.emit_with_target(Instruction::Jump(0), target)?;
+            },
+            "jump0" => {
+                let target = self.next()?;
+                self.emit_with_target(Instruction::JumpOffset(0), target)?;
+            },
+            "native" => {
+                let target = self.next()?;
+                self.emit_with_target(Instruction::Sys(0), target)?;
+            },
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(Instruction::StoreBcd(x))?;
+            },
+            "save" | "load" => {
+                let x = self.register()?;
+                let instruction = if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.register()?;
+                    if token == "save" { Instruction::SaveRange(x, y) } else { Instruction::LoadRange(x, y) }
+                }
+                else if token == "save" {
+                    Instruction::StoreRegisters(x)
+                }
+                else {
+                    Instruction::LoadRegisters(x)
+                };
+                self.emit(instruction)?;
+            },
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit(Instruction::StoreFlags(x))?;
+            },
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit(Instruction::LoadFlags(x))?;
+            },
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(Instruction::Draw(x, y, n))?;
+            },
+            "plane" => {
+                let n = self.nibble()?;
+                self.emit(Instruction::Plane(n))?;
+            },
+            "audio" => self.emit(Instruction::Audio)?,
+            "pitch" | "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let instruction = match token {
+                    "pitch" => Instruction::Pitch(x),
+                    "delay" => Instruction::SetDelay(x),
+                    _ => Instruction::SetSound(x),
+                };
+                self.emit(instruction)?;
+            },
+            "i" => self.index()?,
+            "if" => {
+                let condition = self.condition()?;
+                match self.next()? {
+                    "then" => {
+                        // skip the next statement when the condition doesn't hold
+                        self.emit(condition.skip_when(false))?;
+                        self.statement()?;
+                    },
+                    "begin" => {
+                        // jump over the body when the condition doesn't hold
+                        self.emit(condition.skip_when(true))?;
+                        let jump = self.emit_jump_placeholder()?;
+                        let line = self.line();
+                        self.blocks.push(Block::If { jump, has_else: false, line });
+                    },
+                    other => return self.error(format!("expected 'then' or 'begin', found '{}'", other)),
+                }
+            },
+            "else" => {
+                let jump = match self.blocks.pop() {
+                    Some(Block::If { jump, has_else: false, .. }) => jump,
+                    _ => return self.error(String::from("'else' without 'if ... begin'")),
+                };
+                let end_jump = self.emit_jump_placeholder()?;
+                self.patch(jump, FixupKind::Address, self.address as u16);
+                let line = self.line();
+                self.blocks.push(Block::If { jump: end_jump, has_else: true, line });
+            },
+            "end" => {
+                let jump = match self.blocks.pop() {
+                    Some(Block::If { jump, .. }) => jump,
+                    _ => return self.error(String::from("'end' without 'if ... begin'")),
+                };
+                self.patch(jump, FixupKind::Address, self.address as u16);
+            },
+            "loop" => {
+                let line = self.line();
+                self.blocks.push(Block::Loop { start: self.address as u16, breaks: Vec::new(), line });
+            },
+            "while" => {
+                let condition = self.condition()?;
+                // leave the loop when the condition doesn't hold
+                self.emit(condition.skip_when(true))?;
+                let jump = self.emit_jump_placeholder()?;
+                match self.blocks.iter_mut().rev().find_map(|block| match block {
+                    Block::Loop { breaks, .. } => Some(breaks),
+                    _ => None,
+                }) {
+                    Some(breaks) => breaks.push(jump),
+                    None => return self.error(String::from("'while' outside of a loop")),
+                }
+            },
+            "again" => {
+                let (start, breaks) = match self.blocks.pop() {
+                    Some(Block::Loop { start, breaks, .. }) => (start, breaks),
+                    _ => return self.error(String::from("'again' without 'loop'")),
+                };
+                self.emit(Instruction::Jump(start))?;
+                for jump in breaks {
+                    self.patch(jump, FixupKind::Address, self.address as u16);
+                }
+            },
+            _ if self.parse_register(token).is_some() => self.register_statement(token)?,
+            _ if token.starts_with(':') => return self.error(format!("unsupported directive '{}'", token)),
+            _ => {
+                // numbers and constants are data, any other name (labels included) is a call
+                match self.parse_number(token).or_else(|| self.constants.get(token).copied()) {
+                    Some(value) => {
+                        let byte = self.to_byte(value)?;
+                        self.emit_byte(byte)?;
+                    },
+                    None => self.emit_with_target(Instruction::Call(0), token)?,
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    // vx := ..., vx += ... and the other register operators
+    fn register_statement(&mut self, token: &'a str) -> Result<(), AssembleError> {
+        let x = self.parse_register(token).unwrap_or(0);
+        let operator = self.next()?;
+
+        let instruction = match operator {
+            ":=" => match self.next()? {
+                "random" => Instruction::Random(x, self.byte()?),
+                "delay" => Instruction::LoadDelay(x),
+                "key" => Instruction::WaitKey(x),
+                source => match self.operand(source)? {
+                    Operand::Register(y) => Instruction::Move(x, y),
+                    Operand::Byte(nn) => Instruction::Load(x, nn),
+                },
+            },
+            "+=" => {
+                let source = self.next()?;
+                match self.operand(source)? {
+                    Operand::Register(y) => Instruction::AddRegisters(x, y),
+                    Operand::Byte(nn) => Instruction::Add(x, nn),
+                }
+            },
+            "-=" => {
+                let source = self.next()?;
+                match self.operand(source)? {
+                    Operand::Register(y) => Instruction::Sub(x, y),
+                    // there is no subtract immediate, add the two's complement instead
+                    Operand::Byte(nn) => Instruction::Add(x, nn.wrapping_neg()),
+                }
+            },
+            "=-" => Instruction::SubReverse(x, self.register()?),
+            "|=" => Instruction::Or(x, self.register()?),
+            "&=" => Instruction::And(x, self.register()?),
+            "^=" => Instruction::Xor(x, self.register()?),
+            ">>=" => Instruction::ShiftRight(x, self.register()?),
+            "<<=" => Instruction::ShiftLeft(x, self.register()?),
+            _ => return self.error(format!("unknown operator '{}'", operator)),
+        };
+
+        self.emit(instruction)
+    }
+
+    // i := ..., i += vx
+    fn index(&mut self) -> Result<(), AssembleError> {
+        match self.next()? {
+            ":=" => {},
+            "+=" => {
+                let x = self.register()?;
+                return self.emit(Instruction::AddIndex(x));
+            },
+            other => return self.error(format!("unknown operator '{}' for i", other)),
+        }
+
+        match self.next()? {
+            "hex" => {
+                let x = self.register()?;
+                self.emit(Instruction::LoadFont(x))
+            },
+            "bighex" => {
+                let x = self.register()?;
+                self.emit(Instruction::LoadBigFont(x))
+            },
+            "long" => {
+                let target = self.next()?;
+                let offset = self.offset();
+                self.emit(Instruction::LoadIndexLong(0))?;
+                // the address is the second word
+                self.resolve_target(offset + 2, FixupKind::Long, target)
+            },
+            target => self.emit_with_target(Instruction::LoadIndex(0), target),
+        }
+    }
+
+    // A condition after if or while. <, >, <= and >= have no instruction of their own, they
+    // subtract into vf first and test the borrow flag, just like Octo does.
+    fn condition(&mut self) -> Result<Condition, AssembleError> {
+        let x = self.register()?;
+        let operator = self.next()?;
+
+        let test = match operator {
+            "key" => return Ok(Condition { x, test: Test::Key }),
+            "-key" => return Ok(Condition { x, test: Test::NotKey }),
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => {
+                let source = self.next()?;
+                self.operand(source)?
+            },
+            _ => return self.error(format!("unknown comparison '{}'", operator)),
+        };
+
+        let flag_is = |set: bool| Condition {
+            x: 0xF,
+            test: if set { Test::NotEqual(Operand::Byte(0)) } else { Test::Equal(Operand::Byte(0)) },
+        };
+
+        match operator {
+            "==" => Ok(Condition { x, test: Test::Equal(test) }),
+            "!=" => Ok(Condition { x, test: Test::NotEqual(test) }),
+            // vf is set when x >= operand
+            "<" | ">=" => {
+                self.emit_compare(Operand::Register(x), test)?;
+                Ok(flag_is(operator == ">="))
+            },
+            // vf is set when operand >= x
+            _ => {
+                self.emit_compare(test, Operand::Register(x))?;
+                Ok(flag_is(operator == "<="))
+            },
+        }
+    }
+
+    // Leave vf set when a >= b
+    fn emit_compare(&mut self, a: Operand, b: Operand) -> Result<(), AssembleError> {
+        match (a, b) {
+            (Operand::Register(a), Operand::Byte(b)) => {
+                self.emit(Instruction::Load(0xF, b))?;
+                self.emit(Instruction::SubReverse(0xF, a))
+            },
+            (a, Operand::Register(b)) => {
+                match a {
+                    Operand::Register(a) => self.emit(Instruction::Move(0xF, a))?,
+                    Operand::Byte(a) => self.emit(Instruction::Load(0xF, a))?,
+                }
+                self.emit(Instruction::Sub(0xF, b))
+            },
+            (Operand::Byte(_), Operand::Byte(_)) => self.error(String::from("can't compare two constants")),
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a str, AssembleError> {
+        let name = self.next()?;
+        if self.parse_register(name).is_some() || self.parse_number(name).is_some() {
+            return self.error(format!("'{}' can't be used as a name", name));
+        }
+        Ok(name)
+    }
+
+    fn parse_register(&self, token: &str) -> Option<u8> {
+        if let Some(&register) = self.aliases.get(token) {
+            return Some(register);
+        }
+
+        let mut chars = token.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some('v' | 'V'), Some(digit), None) => digit.to_digit(16).map(|digit| digit as u8),
+            _ => None,
+        }
+    }
+
+    fn register(&mut self) -> Result<u8, AssembleError> {
+        let token = self.next()?;
+        match self.parse_register(token) {
+            Some(register) => Ok(register),
+            None => self.error(format!("expected a register, found '{}'", token)),
+        }
+    }
+
+    fn parse_number(&self, token: &str) -> Option<i64> {
+        let (negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token),
+        };
+
+        let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+            i64::from_str_radix(hex, 16).ok()?
+        }
+        else if let Some(binary) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+            i64::from_str_radix(binary, 2).ok()?
+        }
+        else {
+            digits.parse::<i64>().ok()?
+        };
+
+        Some(if negative { -value } else { value })
+    }
+
+    // A number, constant or already defined label
+    fn parse_value(&self, token: &str) -> Option<i64> {
+        self.parse_number(token)
+            .or_else(|| self.constants.get(token).copied())
+            .or_else(|| self.labels.get(token).map(|&address| address as i64))
+    }
+
+    fn value(&mut self) -> Result<i64, AssembleError> {
+        let token = self.next()?;
+        match self.parse_value(token) {
+            Some(value) => Ok(value),
+            None => self.error(format!("'{}' isn't a number or a constant", token)),
+        }
+    }
+
+    // Bytes can be written signed, -1 is 0xFF
+    fn to_byte(&self, value: i64) -> Result<u8, AssembleError> {
+        if !(-128..=255).contains(&value) {
+            return self.error(format!("{} doesn't fit in a byte", value));
+        }
+        Ok(value as u8)
+    }
+
+    fn byte(&mut self) -> Result<u8, AssembleError> {
+        let value = self.value()?;
+        self.to_byte(value)
+    }
+
+    fn nibble(&mut self) -> Result<u8, AssembleError> {
+        let value = self.value()?;
+        if !(0..=15).contains(&value) {
+            return self.error(format!("{} doesn't fit in a nibble", value));
+        }
+        Ok(value as u8)
+    }
+
+    fn operand(&self, token: &str) -> Result<Operand, AssembleError> {
+        if let Some(register) = self.parse_register(token) {
+            return Ok(Operand::Register(register));
+        }
+        match self.parse_value(token) {
+            Some(value) => Ok(Operand::Byte(self.to_byte(value)?)),
+            None => self.error(format!("expected a register or a number, found '{}'", token)),
+        }
+    }
+
+    // Offset into the ROM of the next byte
+    fn offset(&self) -> usize {
+        self.address - CHIP8_START_OF_PROGRAM
+    }
+
+    fn emit_byte(&mut self, byte: u8) -> Result<(), AssembleError> {
+        if self.address > 0xFFFF {
+            return self.error(String::from("program doesn't fit in 64K"));
+        }
+
+        let offset = self.offset();
+        if offset >= self.rom.len() {
+            self.rom.resize(offset + 1, 0);
+        }
+        self.rom[offset] = byte;
+        self.address += 1;
+        Ok(())
+    }
+
+    fn emit_word(&mut self, word: u16) -> Result<(), AssembleError> {
+        self.emit_byte((word >> 8) as u8)?;
+        self.emit_byte(word as u8)
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> Result<(), AssembleError> {
+        self.emit_word(instruction.opcode())?;
+        match instruction.second_word() {
+            Some(word) => self.emit_word(word),
+            None => Ok(()),
+        }
+    }
+
+    // A jump whose address is filled in once the end of the block is known
+    fn emit_jump_placeholder(&mut self) -> Result<usize, AssembleError> {
+        let offset = self.offset();
+        self.emit(Instruction::Jump(0))?;
+        Ok(offset)
+    }
+
+    // Emit an instruction that takes an address, the address is patched in later if the label
+    // isn't defined yet
+    fn emit_with_target(&mut self, instruction: Instruction, target: &'a str) -> Result<(), AssembleError> {
+        let offset = self.offset();
+        self.emit(instruction)?;
+        self.resolve_target(offset, FixupKind::Address, target)
+    }
+
+    fn resolve_target(&mut self, offset: usize, kind: FixupKind, target: &'a str) -> Result<(), AssembleError> {
+        match self.parse_value(target) {
+            Some(address) => {
+                let address = self.check_address(address, kind, target)?;
+                self.patch(offset, kind, address);
+            },
+            None if self.parse_register(target).is_some() => {
+                return self.error(format!("expected an address, found register '{}'", target));
+            },
+            None => self.fixups.push(Fixup { offset, kind, name: target.to_string(), line: self.line() }),
+        }
+        Ok(())
+    }
+
+    fn check_address(&self, address: i64, kind: FixupKind, name: &str) -> Result<u16, AssembleError> {
+        let limit = match kind {
+            FixupKind::Address => 0xFFF,
+            FixupKind::Long => 0xFFFF,
+        };
+        if !(0..=limit).contains(&address) {
+            let hint = if limit == 0xFFF { ", use i := long" } else { "" };
+            return self.error(format!("'{}' is at {:X}, out of reach{}", name, address, hint));
+        }
+        Ok(address as u16)
+    }
+
+    fn patch(&mut self, offset: usize, kind: FixupKind, address: u16) {
+        let word = (self.rom[offset] as u16) << 8 | self.rom[offset + 1] as u16;
+        let word = match kind {
+            FixupKind::Address => (word & 0xF000) | (address & 0x0FFF),
+            FixupKind::Long => address,
+        };
+        self.rom[offset] = (word >> 8) as u8;
+        self.rom[offset + 1] = word as u8;
+    }
+
+    // Fill in the forward references and the jump to main, and check every block was closed
+    fn finish(mut self) -> Result<Vec<u8>, AssembleError> {
+        if let Some(block) = self.blocks.last() {
+            let (line, message) = match block {
+                Block::If { line, .. } => (*line, "'if ... begin' is missing its 'end'"),
+                Block::Loop { line, .. } => (*line, "'loop' is missing its 'again'"),
+            };
+            return Err(AssembleError { line, message: String::from(message) });
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let address = match self.labels.get(fixup.name.as_str()) {
+                Some(&address) => address,
+                None => return Err(AssembleError { line: fixup.line, message: format!("undefined label '{}'", fixup.name) }),
+            };
+            if matches!(fixup.kind, FixupKind::Address) && address > 0xFFF {
+                return Err(AssembleError {
+                    line: fixup.line,
+                    message: format!("'{}' is at {:X}, out of reach, use i := long", fixup.name, address),
+                });
+            }
+            self.patch(fixup.offset, fixup.kind, address);
+        }
+
+        if self.entry_jump {
+            let main = match self.labels.get("main") {
+                Some(&main) => main,
+                None => return Err(AssembleError { line: 1, message: String::from("no 'main' label, programs start running at main") }),
+            };
+            if main > 0xFFF {
+                return Err(AssembleError { line: 1, message: format!("'main' is at {:X}, out of reach of the jump at 200", main) });
+            }
+            self.patch(0, FixupKind::Address, main);
+        }
+
+        Ok(self.rom)
+    }
+}
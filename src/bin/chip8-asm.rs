@@ -0,0 +1,52 @@
+// Assembler: chip8-asm <source.8o> [-o <rom.ch8>]
+use chip8_emulator_rust::assembler;
+use std::{env, fs, process};
+use std::path::Path;
+
+const USAGE: &str = "usage: chip8-asm <source.8o> [-o <rom.ch8>]";
+
+fn main() {
+   let mut source_path = None;
+   let mut output_path = None;
+
+   let mut args = env::args().skip(1);
+   while let Some(arg) = args.next() {
+      match arg.as_str() {
+         "-o" | "--output" => {
+            output_path = Some(args.next().unwrap_or_else(|| fail("-o needs a value")));
+         },
+         "-h" | "--help" => {
+            println!("{}", USAGE);
+            return;
+         },
+         _ if arg.starts_with('-') => fail(&format!("unknown option '{}'", arg)),
+         _ => source_path = Some(arg),
+      }
+   }
+
+   let source_path = source_path.unwrap_or_else(|| fail("no source file given"));
+   // game.8o assembles to game.ch8 unless told otherwise
+   let output_path = output_path.unwrap_or_else(|| {
+      Path::new(&source_path).with_extension("ch8").to_string_lossy().into_owned()
+   });
+
+   let source = fs::read_to_string(&source_path)
+      .unwrap_or_else(|error| fail(&format!("can't read {}: {}", source_path, error)));
+
+   let rom = match assembler::assemble(&source) {
+      Ok(rom) => rom,
+      Err(error) => {
+         eprintln!("{}: {}", source_path, error);
+         process::exit(1);
+      },
+   };
+
+   fs::write(&output_path, &rom).unwrap_or_else(|error| fail(&format!("can't write {}: {}", output_path, error)));
+   println!("{}: {} bytes", output_path, rom.len());
+}
+
+fn fail(message: &str) -> ! {
+   eprintln!("chip8-asm: {}", message);
+   eprintln!("{}", USAGE);
+   process::exit(2);
+}
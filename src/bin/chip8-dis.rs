@@ -0,0 +1,50 @@
+// Disassembler: chip8-dis <rom> [--platform <name>] [--start <address>]
+use chip8_emulator_rust::{CHIP8_START_OF_PROGRAM, disassembler::Disassembly, quirks::Platform};
+use std::{env, fs, process};
+use std::io::{self, Write};
+
+const USAGE: &str = "usage: chip8-dis <rom> [--platform <vip|chip48|schip|xochip|modern>] [--start <address>]";
+
+fn main() {
+   let mut rom_path = None;
+   let mut platform = Platform::XoChip;
+   let mut start = CHIP8_START_OF_PROGRAM as u16;
+
+   let mut args = env::args().skip(1);
+   while let Some(arg) = args.next() {
+      match arg.as_str() {
+         "--platform" => {
+            let value = args.next().unwrap_or_else(|| fail("--platform needs a value"));
+            platform = value.parse().unwrap_or_else(|error: String| fail(&error));
+         },
+         "--start" => {
+            let value = args.next().unwrap_or_else(|| fail("--start needs a value"));
+            let digits = value.trim_start_matches("0x");
+            start = u16::from_str_radix(digits, 16)
+               .unwrap_or_else(|_| fail(&format!("'{}' is not a hex address", value)));
+         },
+         "-h" | "--help" => {
+            println!("{}", USAGE);
+            return;
+         },
+         _ if arg.starts_with('-') => fail(&format!("unknown option '{}'", arg)),
+         _ => rom_path = Some(arg),
+      }
+   }
+
+   let rom_path = rom_path.unwrap_or_else(|| fail("no ROM given"));
+   let rom = fs::read(&rom_path).unwrap_or_else(|error| fail(&format!("can't read {}: {}", rom_path, error)));
+
+   let disassembly = Disassembly::new(&rom, start, platform.quirks().instruction_set);
+
+   // ignore write errors so piping into head doesn't panic
+   let mut stdout = io::stdout().lock();
+   let _ = writeln!(stdout, "; {} ({} bytes, {})", rom_path, rom.len(), platform);
+   let _ = write!(stdout, "{}", disassembly);
+}
+
+fn fail(message: &str) -> ! {
+   eprintln!("chip8-dis: {}", message);
+   eprintln!("{}", USAGE);
+   process::exit(2);
+}
@@ -0,0 +1,216 @@
+// Headless runner: runs a ROM without a window and dumps the result, for scripts and build servers.
+// Build it without SDL: cargo build --no-default-features --bin chip8-headless
+use chip8_emulator_rust::{CHIP8_START_OF_PROGRAM, drivers::CartridgeDriver, error::ErrorPolicy, instruction::Instruction, png, processor::Processor, quirks::Platform};
+use std::{env, fs, process};
+use std::io::{self, Write};
+
+const USAGE: &str = "\
+usage: chip8-headless <rom> [options]
+  --platform <name>       vip, chip48, schip, xochip or modern (default modern)
+  --ips <n>               instructions per second (default 700)
+  --seed <n>              seed for Cxnn so runs are repeatable (default 0)
+  --frames <n>            stop after n frames (default 600 unless --cycles is given)
+  --cycles <n>            stop after n instructions
+  --until-pc <address>    stop when the pc reaches address (hex)
+  --until-idle            stop when the ROM jumps to itself forever
+  --input <file>          key timeline, lines of '<frame> <key> down|up'
+  --key <frame>:<key>:<down|up>
+                          one timeline entry, can be repeated
+  --ascii                 print the screen as text (the default without --png or --json)
+  --png <file>            save the screen as a PNG
+  --scale <n>             PNG pixel size (default 4)
+  --json <file>           write the registers as JSON, - for stdout
+the run also stops when the ROM exits (00FD) or faults, a fault exits with status 1";
+
+// A key going down or up at the start of a frame
+struct KeyEvent {
+   frame: u64,
+   key: usize,
+   down: bool,
+}
+
+fn main() {
+   let mut rom_path = None;
+   let mut platform = Platform::Modern;
+   let mut instructions_per_second = None;
+   let mut seed = 0;
+   let mut max_frames = None;
+   let mut max_cycles = None;
+   let mut until_pc = None;
+   let mut until_idle = false;
+   let mut timeline = Vec::new();
+   let mut ascii = false;
+   let mut png_path = None;
+   let mut scale = 4;
+   let mut json_path = None;
+
+   let mut args = env::args().skip(1);
+   while let Some(arg) = args.next() {
+      let mut value = |name: &str| args.next().unwrap_or_else(|| fail(&format!("{} needs a value", name)));
+      match arg.as_str() {
+         "--platform" => platform = value("--platform").parse().unwrap_or_else(|error: String| fail(&error)),
+         "--ips" => instructions_per_second = Some(parse_number_between("--ips", &value("--ips"), 1, u32::MAX as u64)),
+         "--seed" => seed = parse_number(&value("--seed")),
+         "--frames" => max_frames = Some(parse_number(&value("--frames"))),
+         "--cycles" => max_cycles = Some(parse_number(&value("--cycles"))),
+         "--until-pc" => until_pc = Some(parse_address(&value("--until-pc"))),
+         "--until-idle" => until_idle = true,
+         "--input" => {
+            let path = value("--input");
+            let script = fs::read_to_string(&path).unwrap_or_else(|error| fail(&format!("can't read {}: {}", path, error)));
+            for (number, line) in script.lines().enumerate() {
+               let line = line.split('#').next().unwrap_or("").trim();
+               if !line.is_empty() {
+                  let fields: Vec<&str> = line.split_whitespace().collect();
+                  timeline.push(parse_key_event(&fields).unwrap_or_else(|| fail(&format!("{}:{}: expected '<frame> <key> down|up'", path, number + 1))));
+               }
+            }
+         },
+         "--key" => {
+            let entry = value("--key");
+            let fields: Vec<&str> = entry.split(':').collect();
+            timeline.push(parse_key_event(&fields).unwrap_or_else(|| fail(&format!("'{}' should look like <frame>:<key>:<down|up>", entry))));
+         },
+         "--ascii" => ascii = true,
+         "--png" => png_path = Some(value("--png")),
+         "--scale" => scale = parse_number(&value("--scale")) as usize,
+         "--json" => json_path = Some(value("--json")),
+         "-h" | "--help" => {
+            println!("{}", USAGE);
+            return;
+         },
+         _ if arg.starts_with('-') => fail(&format!("unknown option '{}'", arg)),
+         _ => rom_path = Some(arg),
+      }
+   }
+
+   let rom_path = rom_path.unwrap_or_else(|| fail("no ROM given"));
+   let cartridge = CartridgeDriver::open(&rom_path).unwrap_or_else(|error| fail(&format!("can't read {}: {}", rom_path, error)));
+   if max_frames.is_none() && max_cycles.is_none() {
+      max_frames = Some(600);
+   }
+   if png_path.is_none() && json_path.is_none() {
+      ascii = true;
+   }
+   timeline.sort_by_key(|event| event.frame);
+
+   let mut processor = Processor::new(platform.quirks());
+   processor.set_error_policy(ErrorPolicy::Halt);
+   processor.seed_random(seed);
+   if let Some(instructions_per_second) = instructions_per_second {
+      processor.set_instructions_per_second(instructions_per_second as u32);
+   }
+   processor.load(cartridge.rom, cartridge.size, CHIP8_START_OF_PROGRAM);
+
+   let mut keypad = [false; 16];
+   let mut events = timeline.iter().peekable();
+   let mut frames = 0u64;
+   let mut cycles = 0u64;
+
+   'run: while max_frames.is_none_or(|max_frames| frames < max_frames) {
+      while let Some(event) = events.next_if(|event| event.frame <= frames) {
+         keypad[event.key] = event.down;
+      }
+
+      for _ in 0..processor.instructions_for_next_frame() {
+         if max_cycles.is_some_and(|max_cycles| cycles >= max_cycles)
+            || until_pc == Some(processor.pc())
+            || (until_idle && is_idle(&processor))
+            || processor.exited()
+         {
+            break 'run;
+         }
+
+         if processor.cycle(keypad).is_err() {
+            break 'run;
+         }
+         cycles += 1;
+      }
+
+      processor.tick_timers();
+      frames += 1;
+   }
+
+   if ascii {
+      print!("{}", processor.framebuffer());
+   }
+   if let Some(path) = png_path {
+      fs::write(&path, png::encode(processor.framebuffer(), scale))
+         .unwrap_or_else(|error| fail(&format!("can't write {}: {}", path, error)));
+   }
+   if let Some(path) = json_path {
+      let json = registers_json(&processor, frames, cycles);
+      let result = if path == "-" { io::stdout().write_all(json.as_bytes()) } else { fs::write(&path, json) };
+      result.unwrap_or_else(|error| fail(&format!("can't write {}: {}", path, error)));
+   }
+
+   if let Some(error) = processor.halted() {
+      eprintln!("chip8-headless: {}", error);
+      process::exit(1);
+   }
+}
+
+// A jump to its own address, the usual way a test ROM stops once it's done
+fn is_idle(processor: &Processor) -> bool {
+   let pc = processor.pc();
+   processor.read_opcode(pc).is_ok_and(|opcode| Instruction::decode(opcode, 0) == Instruction::Jump(pc))
+}
+
+fn registers_json(processor: &Processor, frames: u64, cycles: u64) -> String {
+   let list = |values: Vec<String>| values.join(", ");
+   let halted = match processor.halted() {
+      Some(error) => format!("\"{}\"", error),
+      None => String::from("null"),
+   };
+
+   format!("{{\n  \"frames\": {},\n  \"cycles\": {},\n  \"pc\": {},\n  \"i\": {},\n  \"v\": [{}],\n  \"sp\": {},\n  \"stack\": [{}],\n  \"dt\": {},\n  \"st\": {},\n  \"exited\": {},\n  \"halted\": {}\n}}\n",
+      frames,
+      cycles,
+      processor.pc(),
+      processor.index(),
+      list(processor.registers().iter().map(|value| value.to_string()).collect()),
+      processor.stack().len(),
+      list(processor.stack().iter().map(|address| address.to_string()).collect()),
+      processor.delay_timer(),
+      processor.sound_timer(),
+      processor.exited(),
+      halted)
+}
+
+// [frame, key, down|up]
+fn parse_key_event(fields: &[&str]) -> Option<KeyEvent> {
+   match fields {
+      [frame, key, state] => Some(KeyEvent {
+         frame: frame.parse().ok()?,
+         key: usize::from_str_radix(key, 16).ok().filter(|&key| key < 16)?,
+         down: match *state {
+            "down" => true,
+            "up" => false,
+            _ => return None,
+         },
+      }),
+      _ => None,
+   }
+}
+
+fn parse_number(value: &str) -> u64 {
+   value.parse().unwrap_or_else(|_| fail(&format!("'{}' is not a number", value)))
+}
+
+fn parse_number_between(name: &str, value: &str, min: u64, max: u64) -> u64 {
+   let number = parse_number(value);
+   if !(min..=max).contains(&number) {
+      fail(&format!("{} must be between {} and {}, got {}", name, min, max, value));
+   }
+   number
+}
+
+fn parse_address(value: &str) -> u16 {
+   u16::from_str_radix(value.trim_start_matches("0x"), 16).unwrap_or_else(|_| fail(&format!("'{}' is not a hex address", value)))
+}
+
+fn fail(message: &str) -> ! {
+   eprintln!("chip8-headless: {}", message);
+   eprintln!("{}", USAGE);
+   process::exit(2);
+}
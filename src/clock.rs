@@ -0,0 +1,43 @@
+// CPU clock, counted in frames of the 60Hz delay/sound timers instead of wall-clock time
+// so a run is the same every time.
+
+pub const TIMER_HZ: u32 = 60;
+pub const DEFAULT_INSTRUCTIONS_PER_SECOND: u32 = 700;
+
+pub struct Clock {
+    instructions_per_second: u32,
+    // instructions owed from earlier frames when the rate isn't a multiple of 60
+    remainder: u32,
+}
+
+impl Clock {
+    pub fn new(instructions_per_second: u32) -> Self {
+        Self {
+            instructions_per_second,
+            remainder: 0,
+        }
+    }
+
+    pub fn instructions_per_second(&self) -> u32 {
+        self.instructions_per_second
+    }
+
+    pub fn set_instructions_per_second(&mut self, instructions_per_second: u32) {
+        self.instructions_per_second = instructions_per_second;
+        self.remainder = 0;
+    }
+
+    // How many instructions to run before the next timer tick
+    // e.g. 1000 ips runs 16, 16, 17, 16, 16, 17... instructions per frame
+    pub fn instructions_for_next_frame(&mut self) -> u32 {
+        let total = self.instructions_per_second + self.remainder;
+        self.remainder = total % TIMER_HZ;
+        total / TIMER_HZ
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new(DEFAULT_INSTRUCTIONS_PER_SECOND)
+    }
+}
@@ -0,0 +1,413 @@
+// Command line debugger. It sits between the frontend and the processor, runs a frame one
+// instruction at a time and drops into a prompt on breakpoints, after a step or when the
+// program faults.
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+use crate::instruction::Instruction;
+use crate::processor::Processor;
+use crate::watchpoint::{Target, Trigger, Watchpoints};
+
+const HELP: &str = "\
+break [addr]        set a breakpoint (hex), or list them without an address
+delete <addr>       remove a breakpoint
+watch <addr> [len] [r|w|rw]
+                    pause when RAM is accessed (default 1 byte, r/w)
+watch <reg>         pause when V0-VF, I, DT or ST is written or a timer ticks
+watch               list the watchpoints
+unwatch <id>        remove a watchpoint
+step [n]            run n instructions (default 1)
+continue            run until a breakpoint
+finish              run until the current subroutine returns
+regs                show the registers
+stack               show the return addresses
+mem <addr> <len>    dump len bytes from addr (hex address, decimal length)
+set <reg> <value>   set V0-VF, I, PC, DT or ST (0x for hex, decimal otherwise)
+disasm [n]          n instructions around the pc (default 5 either side)
+quit                stop the emulator
+an empty line repeats the last command";
+
+// Something a register can be set to with `set`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    V(usize),
+    I,
+    Pc,
+    Dt,
+    St,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Break(Option<u16>),
+    Delete(u16),
+    Watch(Option<Target>),
+    Unwatch(usize),
+    Step(usize),
+    Continue,
+    Finish,
+    Regs,
+    Stack,
+    Mem(u16, usize),
+    Set(Register, u16),
+    Disasm(usize),
+    Help,
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+        let arg = |index: usize| args.get(index).copied().ok_or_else(|| format!("'{}' needs more arguments", name));
+
+        let command = match name {
+            "break" | "b" => Command::Break(args.first().map(|&address| parse_address(address)).transpose()?),
+            "delete" | "d" => Command::Delete(parse_address(arg(0)?)?),
+            "watch" | "w" => Command::Watch(if args.is_empty() { None } else { Some(parse_watch(&args)?) }),
+            "unwatch" => Command::Unwatch(parse_count(arg(0)?)?),
+            "step" | "s" => Command::Step(args.first().map(|&count| parse_count(count)).transpose()?.unwrap_or(1)),
+            "continue" | "c" => Command::Continue,
+            "finish" | "f" => Command::Finish,
+            "regs" | "r" => Command::Regs,
+            "stack" | "bt" => Command::Stack,
+            "mem" | "x" => Command::Mem(parse_address(arg(0)?)?, parse_count(arg(1)?)?),
+            "set" => Command::Set(parse_register(arg(0)?)?, parse_value(arg(1)?)?),
+            "disasm" | "l" => Command::Disasm(args.first().map(|&count| parse_count(count)).transpose()?.unwrap_or(5)),
+            "help" | "h" | "?" => Command::Help,
+            "quit" | "q" => Command::Quit,
+            _ => return Err(format!("unknown command '{}', try help", name)),
+        };
+
+        Ok(command)
+    }
+}
+
+// Why execution is allowed to carry on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    // waiting at the prompt before the next instruction
+    Paused,
+    // instructions left before pausing again
+    Stepping(usize),
+    // pause once the stack is shallower than this
+    Finishing(usize),
+    Running,
+}
+
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Watchpoints,
+    mode: Mode,
+    last_command: Option<Command>,
+}
+
+impl Debugger {
+    // The debugger starts out running, call pause to get a prompt before the first instruction
+    pub fn new() -> Self {
+        Self {
+            breakpoints: BTreeSet::new(),
+            watchpoints: Watchpoints::new(),
+            mode: Mode::Running,
+            last_command: None,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.remove(&address);
+    }
+
+    // Watchpoints added here pause at the prompt unless they were given a callback
+    pub fn watchpoints_mut(&mut self) -> &mut Watchpoints {
+        &mut self.watchpoints
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.mode == Mode::Paused
+    }
+
+    // Stop before the next instruction
+    pub fn pause(&mut self) {
+        self.mode = Mode::Paused;
+    }
+
+    // Run one frame like Processor::run_frame, prompting on input whenever execution stops.
+    // Returns false once the user quits (or input is closed).
+    pub fn run_frame<R: BufRead, W: Write>(&mut self, processor: &mut Processor, keypad: [bool; 16], input: &mut R, output: &mut W) -> bool {
+        for _ in 0..processor.instructions_for_next_frame() {
+            if self.should_stop(processor) {
+                self.mode = Mode::Paused;
+                if !self.prompt(processor, input, output) {
+                    return false;
+                }
+            }
+
+            self.watchpoints.before(processor);
+            if let Err(error) = processor.cycle(keypad) {
+                let _ = writeln!(output, "{}", error);
+                self.mode = Mode::Paused;
+                continue;
+            }
+
+            let hits = self.watchpoints.after(processor);
+            if !hits.is_empty() {
+                for hit in hits {
+                    let _ = writeln!(output, "{}", hit);
+                }
+                self.mode = Mode::Paused;
+                continue;
+            }
+
+            if let Mode::Stepping(count) = self.mode {
+                self.mode = Mode::Stepping(count - 1);
+            }
+        }
+        // a timer watchpoint pauses before the next frame's first instruction
+        let hits = self.watchpoints.tick_timers(processor);
+        for hit in &hits {
+            let _ = writeln!(output, "{}", hit);
+        }
+        if !hits.is_empty() {
+            self.mode = Mode::Paused;
+        }
+
+        true
+    }
+
+    fn should_stop(&self, processor: &Processor) -> bool {
+        match self.mode {
+            Mode::Paused | Mode::Stepping(0) => true,
+            Mode::Finishing(depth) if processor.stack().len() < depth => true,
+            _ => self.breakpoints.contains(&processor.pc()),
+        }
+    }
+
+    // Read and run commands until one of them resumes execution
+    fn prompt<R: BufRead, W: Write>(&mut self, processor: &mut Processor, input: &mut R, output: &mut W) -> bool {
+        let _ = writeln!(output, "{}", self.current_instruction(processor));
+
+        while self.mode == Mode::Paused {
+            let _ = write!(output, "(chip8) ");
+            let _ = output.flush();
+
+            let mut line = String::new();
+            match input.read_line(&mut line) {
+                Ok(0) | Err(_) => return false,
+                Ok(_) => {},
+            }
+
+            let command = if line.trim().is_empty() {
+                match self.last_command {
+                    Some(command) => Ok(command),
+                    None => continue,
+                }
+            }
+            else {
+                Command::parse(&line)
+            };
+
+            match command {
+                Ok(Command::Quit) => return false,
+                Ok(command) => {
+                    self.last_command = Some(command);
+                    let _ = self.execute(command, processor, output);
+                },
+                Err(error) => {
+                    let _ = writeln!(output, "{}", error);
+                },
+            }
+        }
+
+        // continuing after a fault runs the faulting instruction again
+        if processor.halted().is_some() {
+            processor.resume();
+        }
+
+        true
+    }
+
+    // Run a single command, commands that resume execution just change the mode
+    pub fn execute<W: Write>(&mut self, command: Command, processor: &mut Processor, output: &mut W) -> std::io::Result<()> {
+        match command {
+            Command::Break(Some(address)) => {
+                self.add_breakpoint(address);
+                writeln!(output, "breakpoint at {:03X}", address)?;
+            },
+            Command::Break(None) => {
+                for address in &self.breakpoints {
+                    writeln!(output, "{:03X}", address)?;
+                }
+            },
+            Command::Delete(address) => self.remove_breakpoint(address),
+            Command::Watch(Some(target)) => {
+                let id = self.watchpoints.add(target);
+                writeln!(output, "watchpoint {}: {}", id, target)?;
+            },
+            Command::Watch(None) => {
+                for (id, target) in self.watchpoints.iter() {
+                    writeln!(output, "{}  {}", id, target)?;
+                }
+            },
+            Command::Unwatch(id) => {
+                if !self.watchpoints.remove(id) {
+                    writeln!(output, "no watchpoint {}", id)?;
+                }
+            },
+            Command::Step(count) => self.mode = Mode::Stepping(count.max(1)),
+            Command::Continue => self.mode = Mode::Running,
+            Command::Finish => {
+                if processor.stack().is_empty() {
+                    writeln!(output, "not in a subroutine")?;
+                }
+                else {
+                    self.mode = Mode::Finishing(processor.stack().len());
+                }
+            },
+            Command::Regs => {
+                let registers = processor.registers();
+                for (x, value) in registers.iter().enumerate() {
+                    write!(output, "V{:X}={:02X}{}", x, value, if x % 8 == 7 { "\n" } else { " " })?;
+                }
+                writeln!(output, "I={:04X} PC={:04X} SP={:X} DT={:02X} ST={:02X}{}",
+                    processor.index(), processor.pc(), processor.stack().len(),
+                    processor.delay_timer(), processor.sound_timer(),
+                    if processor.waiting_for_key() { " (waiting for a key)" } else { "" })?;
+            },
+            Command::Stack => {
+                if processor.stack().is_empty() {
+                    writeln!(output, "stack is empty")?;
+                }
+                for (depth, address) in processor.stack().iter().enumerate().rev() {
+                    writeln!(output, "#{:X}  {:03X}", depth, address)?;
+                }
+            },
+            Command::Mem(address, length) => {
+                let memory = processor.memory();
+                for row in (0..length).step_by(16) {
+                    write!(output, "{:04X}:", address as usize + row)?;
+                    for offset in row..length.min(row + 16) {
+                        match memory.peek(address as usize + offset) {
+                            Ok(byte) => write!(output, " {:02X}", byte)?,
+                            Err(_) => write!(output, " --")?,
+                        }
+                    }
+                    writeln!(output)?;
+                }
+            },
+            Command::Set(register, value) => {
+                match register {
+                    Register::V(x) => processor.set_register(x, value as u8),
+                    Register::I => processor.set_index(value),
+                    Register::Pc => processor.set_pc(value),
+                    Register::Dt => processor.set_delay_timer(value as u8),
+                    Register::St => processor.set_sound_timer(value as u8),
+                }
+            },
+            Command::Disasm(count) => {
+                let pc = processor.pc();
+                // assume instructions before the pc are 2 bytes apart, they usually are
+                let span = count.saturating_mul(2).min(0xFFFF) as u16;
+                let end = pc.saturating_add(span);
+                let mut address = pc.saturating_sub(span);
+                while address <= end {
+                    let instruction = self.instruction_at(processor, address);
+                    let marker = if address == pc { "=>" } else if self.breakpoints.contains(&address) { " *" } else { "  " };
+                    writeln!(output, "{} {:03X}:  {}", marker, address, instruction.map_or(String::from("??"), |i| i.to_string()))?;
+                    address = match address.checked_add(instruction.map_or(2, |i| i.size())) {
+                        Some(next) => next,
+                        None => break,
+                    };
+                }
+            },
+            Command::Help => writeln!(output, "{}", HELP)?,
+            Command::Quit => {},
+        }
+
+        Ok(())
+    }
+
+    fn instruction_at(&self, processor: &Processor, address: u16) -> Option<Instruction> {
+        processor.read_instruction(address).ok()
+    }
+
+    fn current_instruction(&self, processor: &Processor) -> String {
+        let pc = processor.pc();
+        match self.instruction_at(processor, pc) {
+            Some(instruction) => format!("{:03X}:  {:04X}  {}", pc, instruction.opcode(), instruction),
+            None => format!("{:03X}:  out of memory", pc),
+        }
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Addresses are always hex, with or without 0x
+fn parse_address(text: &str) -> Result<u16, String> {
+    let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("'{}' is not a hex address", text))
+}
+
+fn parse_count(text: &str) -> Result<usize, String> {
+    text.parse().map_err(|_| format!("'{}' is not a number", text))
+}
+
+// Values are hex with 0x, decimal without
+fn parse_value(text: &str) -> Result<u16, String> {
+    let value = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(digits) => u16::from_str_radix(digits, 16),
+        None => text.parse(),
+    };
+    value.map_err(|_| format!("'{}' is not a value", text))
+}
+
+// watch V3, watch I, watch 3A0, watch 3A0 4 w
+fn parse_watch(args: &[&str]) -> Result<Target, String> {
+    if let Ok(register) = parse_register(args[0]) {
+        return match register {
+            Register::V(x) => Ok(Target::Register(x)),
+            Register::I => Ok(Target::Index),
+            Register::Dt => Ok(Target::DelayTimer),
+            Register::St => Ok(Target::SoundTimer),
+            Register::Pc => Err(String::from("can't watch the pc, use break")),
+        };
+    }
+
+    let address = parse_address(args[0])? as usize;
+    let mut length = 1;
+    let mut trigger = Trigger::ReadWrite;
+    for &arg in &args[1..] {
+        match arg {
+            "r" => trigger = Trigger::Read,
+            "w" => trigger = Trigger::Write,
+            "rw" => trigger = Trigger::ReadWrite,
+            _ => length = parse_count(arg)?,
+        }
+    }
+    if length > 0x10000 - address {
+        return Err(format!("{} bytes from {:03X} runs past the end of memory", length, address));
+    }
+
+    Ok(Target::Memory { address, length, trigger })
+}
+
+fn parse_register(text: &str) -> Result<Register, String> {
+    let upper = text.to_ascii_uppercase();
+    match upper.as_str() {
+        "I" => Ok(Register::I),
+        "PC" => Ok(Register::Pc),
+        "DT" => Ok(Register::Dt),
+        "ST" => Ok(Register::St),
+        _ => match upper.strip_prefix('V').and_then(|digit| usize::from_str_radix(digit, 16).ok()) {
+            Some(x) if x < 16 => Ok(Register::V(x)),
+            _ => Err(format!("unknown register '{}'", text)),
+        },
+    }
+}
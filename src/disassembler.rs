@@ -0,0 +1,185 @@
+// Turns a ROM back into a listing. Code is found by following jumps, calls and skips from the
+// entry point, everything that is never reached is shown as data with a sprite preview.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use crate::instruction::Instruction;
+use crate::quirks::InstructionSet;
+
+pub struct Disassembly {
+    start: u16,
+    rom: Vec<u8>,
+    instruction_set: InstructionSet,
+    // addresses where an instruction starts
+    code: BTreeSet<u16>,
+    labels: BTreeMap<u16, String>,
+    // Bnnn targets depend on a register, so the analysis can't follow them
+    computed_jumps: BTreeSet<u16>,
+}
+
+impl Disassembly {
+    // Walk every path reachable from start (normally 0x200)
+    pub fn new(rom: &[u8], start: u16, instruction_set: InstructionSet) -> Self {
+        let mut disassembly = Self {
+            start,
+            rom: rom.to_vec(),
+            instruction_set,
+            code: BTreeSet::new(),
+            labels: BTreeMap::new(),
+            computed_jumps: BTreeSet::new(),
+        };
+
+        disassembly.labels.insert(start, String::from("start"));
+
+        let mut pending = vec![start];
+        while let Some(address) = pending.pop() {
+            if disassembly.code.contains(&address) {
+                continue;
+            }
+
+            let instruction = match disassembly.instruction_at(address) {
+                Some(Instruction::Unknown(_)) | None => continue,
+                Some(instruction) => instruction,
+            };
+            disassembly.code.insert(address);
+
+            let next = address.wrapping_add(instruction.size());
+
+            match instruction {
+                Instruction::Jump(target) => {
+                    disassembly.add_label(target, "label");
+                    pending.push(target);
+                },
+                Instruction::Call(target) => {
+                    disassembly.add_label(target, "sub");
+                    pending.push(target);
+                    pending.push(next);
+                },
+                Instruction::JumpOffset(target) => {
+                    disassembly.add_label(target, "table");
+                    disassembly.computed_jumps.insert(address);
+                },
+                Instruction::Return | Instruction::Exit => {},
+                Instruction::LoadIndex(target) | Instruction::LoadIndexLong(target) => {
+                    disassembly.add_label(target, "data");
+                    pending.push(next);
+                },
+                _ if instruction.is_skip() => {
+                    pending.push(next);
+                    // the skipped instruction can be the 4 byte F000 nnnn
+                    let skipped = disassembly.instruction_at(next).map_or(2, |i| i.size());
+                    pending.push(next.wrapping_add(skipped));
+                },
+                _ => pending.push(next),
+            }
+        }
+
+        disassembly
+    }
+
+    pub fn is_code(&self, address: u16) -> bool {
+        self.code.contains(&address)
+    }
+
+    pub fn label(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(|label| label.as_str())
+    }
+
+    // one past the last byte, can be 0x10000 for a full XO-CHIP ROM
+    fn end(&self) -> usize {
+        self.start as usize + self.rom.len()
+    }
+
+    fn byte(&self, address: u16) -> Option<u8> {
+        if address < self.start {
+            return None;
+        }
+        self.rom.get((address - self.start) as usize).copied()
+    }
+
+    fn word(&self, address: u16) -> Option<u16> {
+        Some((self.byte(address)? as u16) << 8 | self.byte(address.checked_add(1)?)? as u16)
+    }
+
+    fn instruction_at(&self, address: u16) -> Option<Instruction> {
+        let next = address.checked_add(2).and_then(|next| self.word(next));
+        match Instruction::decode_for(self.word(address)?, next.unwrap_or(0), self.instruction_set) {
+            // the long load needs its address word too
+            Instruction::LoadIndexLong(_) => next.map(Instruction::LoadIndexLong),
+            instruction => Some(instruction),
+        }
+    }
+
+    // Only label addresses inside the ROM, anything else is printed as a number
+    fn add_label(&mut self, address: u16, prefix: &str) {
+        if address >= self.start && (address as usize) < self.end() {
+            self.labels.entry(address).or_insert_with(|| format!("{}_{:03X}", prefix, address));
+        }
+    }
+
+    fn target(&self, address: u16) -> String {
+        match self.label(address) {
+            Some(label) => label.to_string(),
+            None => format!("0x{:03X}", address),
+        }
+    }
+
+    // Mnemonic with label names in place of the addresses they point to
+    fn format_instruction(&self, instruction: Instruction) -> String {
+        match instruction {
+            Instruction::Jump(target) => format!("JP {}", self.target(target)),
+            Instruction::Call(target) => format!("CALL {}", self.target(target)),
+            Instruction::LoadIndex(target) => format!("LD I, {}", self.target(target)),
+            Instruction::JumpOffset(target) => format!("JP V0, {}", self.target(target)),
+            Instruction::LoadIndexLong(target) => format!("LD I, {}", self.target(target)),
+            _ => instruction.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut address = self.start as usize;
+        let mut in_data = false;
+
+        while address < self.end() {
+            let address_u16 = address as u16;
+            if let Some(label) = self.label(address_u16) {
+                writeln!(f)?;
+                writeln!(f, "{}:", label)?;
+            }
+
+            match self.instruction_at(address_u16).filter(|_| self.is_code(address_u16)) {
+                Some(instruction) => {
+                    in_data = false;
+                    let mut bytes = format!("{:04X}", instruction.opcode());
+                    if let Some(word) = instruction.second_word() {
+                        bytes += &format!(" {:04X}", word);
+                    }
+
+                    write!(f, "    {:03X}:  {:<10} {}", address, bytes, self.format_instruction(instruction))?;
+                    if self.computed_jumps.contains(&address_u16) {
+                        write!(f, "    ; computed jump, target not followed")?;
+                    }
+                    writeln!(f)?;
+
+                    address += instruction.size() as usize;
+                },
+                None => {
+                    if !in_data && self.label(address_u16).is_none() {
+                        writeln!(f)?;
+                    }
+                    in_data = true;
+
+                    // one byte per line, with the sprite row it would draw
+                    let byte = self.byte(address_u16).unwrap_or(0);
+                    let sprite: String = (0..8).map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' }).collect();
+                    writeln!(f, "    {:03X}:  {:02X}         DB 0x{:02X}    {}", address, byte, byte, sprite)?;
+
+                    address += 1;
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
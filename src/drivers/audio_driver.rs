@@ -0,0 +1,166 @@
+use std::f32::consts::PI;
+use std::str::FromStr;
+
+use sdl2;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+pub const DEFAULT_FREQUENCY: f32 = 440.0;
+pub const DEFAULT_VOLUME: f32 = 0.25;
+
+const SAMPLE_RATE: i32 = 44100;
+// the tone fades in and out over this long instead of starting or stopping mid wave, which clicks
+const RAMP_SECONDS: f32 = 0.005;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    // phase goes from 0 to 1 over one period
+    fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Sine => (phase * 2.0 * PI).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        }
+    }
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "square" => Ok(Waveform::Square),
+            "sine" => Ok(Waveform::Sine),
+            "triangle" => Ok(Waveform::Triangle),
+            _ => Err(format!("unknown waveform '{}', expected one of: square, sine, triangle", s)),
+        }
+    }
+}
+
+// Runs on SDL's audio thread, the driver only changes it while holding the device lock
+struct Tone {
+    waveform: Waveform,
+    volume: f32,
+    // how far through a period each sample moves the phase
+    phase_step: f32,
+    phase: f32,
+    // envelope between 0 and 1, moves toward 1 while playing and toward 0 otherwise
+    gain: f32,
+    ramp_step: f32,
+    playing: bool,
+}
+
+impl AudioCallback for Tone {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            let target = if self.playing { 1.0 } else { 0.0 };
+            if self.gain < target {
+                self.gain = (self.gain + self.ramp_step).min(target);
+            }
+            else if self.gain > target {
+                self.gain = (self.gain - self.ramp_step).max(target);
+            }
+
+            if self.gain == 0.0 {
+                // restart from the beginning of a period next time the tone starts
+                self.phase = 0.0;
+                *sample = 0.0;
+                continue;
+            }
+
+            *sample = self.waveform.sample(self.phase) * self.volume * self.gain;
+            self.phase = (self.phase + self.phase_step) % 1.0;
+        }
+    }
+}
+
+pub struct AudioDriver {
+    device: AudioDevice<Tone>,
+    sample_rate: f32,
+    muted: bool,
+    // whether the sound timer is running
+    playing: bool,
+    // whether the tone is on, playing and not muted
+    audible: bool,
+}
+
+impl AudioDriver {
+    // Fails when there is no audio device, the caller can carry on without sound
+    pub fn new(sdl_context: &sdl2::Sdl) -> Result<Self, String> {
+        let audio_subsystem = sdl_context.audio()?;
+        let desired_spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: Some(512),
+        };
+
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
+            let sample_rate = spec.freq as f32;
+            Tone {
+                waveform: Waveform::Square,
+                volume: DEFAULT_VOLUME,
+                phase_step: DEFAULT_FREQUENCY / sample_rate,
+                phase: 0.0,
+                gain: 0.0,
+                ramp_step: 1.0 / (RAMP_SECONDS * sample_rate),
+                playing: false,
+            }
+        })?;
+        let sample_rate = device.spec().freq as f32;
+
+        // the device always runs, silence is written while the tone is off so the ramps can play out
+        device.resume();
+
+        Ok(AudioDriver { device, sample_rate, muted: false, playing: false, audible: false })
+    }
+
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.device.lock().waveform = waveform;
+    }
+
+    // frequency of the tone in Hz
+    pub fn set_frequency(&mut self, frequency: f32) {
+        let phase_step = frequency / self.sample_rate;
+        self.device.lock().phase_step = phase_step;
+    }
+
+    // volume between 0 and 1
+    pub fn set_volume(&mut self, volume: f32) {
+        self.device.lock().volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        self.set_playing(self.playing);
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.set_muted(!self.muted);
+    }
+
+    // Called once per frame with the sound timer, the tone plays while it is above 0
+    pub fn update(&mut self, sound_timer: u8) {
+        self.set_playing(sound_timer > 0);
+    }
+
+    fn set_playing(&mut self, playing: bool) {
+        self.playing = playing;
+        // only take the lock when the tone actually starts or stops
+        let audible = playing && !self.muted;
+        if audible != self.audible {
+            self.audible = audible;
+            self.device.lock().playing = audible;
+        }
+    }
+}
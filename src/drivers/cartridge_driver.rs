@@ -1,6 +1,6 @@
 //Taken from https://github.com/starrhorne/chip8-rust/blob/master/src/drivers/cartridge_driver.rs
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::{self, prelude::*};
 use crate::Program;
 
 pub struct CartridgeDriver {
@@ -10,20 +10,21 @@ pub struct CartridgeDriver {
 
 impl CartridgeDriver {
     pub fn new(filename: &str) -> Self {
-        let mut f = File::open(filename).expect("file not found");
+        Self::open(filename).expect("file not found")
+    }
+
+    // Like new, but a missing or unreadable file is an error instead of a panic
+    pub fn open(filename: &str) -> io::Result<Self> {
+        let mut f = File::open(filename)?;
 
         // creates buffer initialized to 0
-        let mut buffer = [0u8; crate::CHIP8_PROGRAM_SIZE];
+        let mut buffer = [0u8; crate::XOCHIP_PROGRAM_SIZE];
 
-        let bytes_read = if let Ok(bytes_read) = f.read(&mut buffer) {
-            bytes_read
-        } else {
-            0
-        };
+        let bytes_read = f.read(&mut buffer)?;
 
-        CartridgeDriver {
+        Ok(CartridgeDriver {
             rom: buffer,
             size: bytes_read,
-        }
+        })
     }
-}
\ No newline at end of file
+}
@@ -4,59 +4,69 @@ use sdl2::rect::Rect;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
 
-use crate::CHIP8_SCREEN_WIDTH;
-use crate::CHIP8_SCREEN_HEIGHT;
+use crate::SCHIP_SCREEN_WIDTH;
+use crate::SCHIP_SCREEN_HEIGHT;
+use crate::framebuffer::{FrameBuffer, PALETTE};
 
-const SCALE_FACTOR: u32 = 7;
-const SCREEN_WIDTH: u32 = (CHIP8_SCREEN_WIDTH as u32) * SCALE_FACTOR;
-const SCREEN_HEIGHT: u32 = (CHIP8_SCREEN_HEIGHT as u32) * SCALE_FACTOR;
+// the window fits the SUPER-CHIP hires screen, lores pixels are drawn twice as big
+pub const DEFAULT_SCALE: u32 = 5;
 
 pub struct DisplayDriver {
     canvas: Canvas<Window>,
+    // window size in pixels at the chosen scale
+    width: u32,
 }
 
 impl DisplayDriver {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+    // scale is the size of a hires pixel, fullscreen stretches that picture over the whole desktop,
+    // with vsync draw waits for the display to refresh
+    pub fn new(sdl_context: &sdl2::Sdl, title: &str, scale: u32, fullscreen: bool, vsync: bool) -> Self {
+        let width = SCHIP_SCREEN_WIDTH as u32 * scale;
+        let height = SCHIP_SCREEN_HEIGHT as u32 * scale;
+
         let video_subsys = sdl_context.video().unwrap();
-        let window = video_subsys
-            .window(
-                "rust-sdl2_gfx: draw line & FPSManager",
-                SCREEN_WIDTH,
-                SCREEN_HEIGHT,
-            )
-            .position_centered()
-            .opengl()
-            .build()
-            .unwrap();
-
-        let mut canvas = window.into_canvas().build().unwrap();
+        let mut builder = video_subsys.window(title, width, height);
+        builder.position_centered().opengl();
+        if fullscreen {
+            builder.fullscreen_desktop();
+        }
+        let window = builder.build().unwrap();
+
+        let mut canvas_builder = window.into_canvas();
+        if vsync {
+            canvas_builder = canvas_builder.present_vsync();
+        }
+        let mut canvas = canvas_builder.build().unwrap();
+        // letterboxed to the window when it isn't the size we asked for
+        let _ = canvas.set_logical_size(width, height);
 
         canvas.set_draw_color(pixels::Color::RGB(100, 100, 100));
         canvas.clear();
         canvas.present();
 
-        DisplayDriver { canvas: canvas }
+        DisplayDriver { canvas, width }
     }
 
-    pub fn draw(&mut self, pixels: &[[bool; CHIP8_SCREEN_WIDTH]; CHIP8_SCREEN_HEIGHT]) {
-        for (y, row) in pixels.iter().enumerate() {
-            for (x, &col) in row.iter().enumerate() {
-                let x = (x as u32) * SCALE_FACTOR;
-                let y = (y as u32) * SCALE_FACTOR;
+    // Draw the framebuffer scaled up to fill the window
+    pub fn draw(&mut self, framebuffer: &FrameBuffer) {
+        let scale = self.width / framebuffer.width() as u32;
 
-                self.canvas.set_draw_color(color(u8::from(col)));
+        for (y, row) in framebuffer.rows().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                let x = (x as u32) * scale;
+                let y = (y as u32) * scale;
+
+                self.canvas.set_draw_color(color(pixel));
                 let _ = self.canvas
-                    .fill_rect(Rect::new(x as i32, y as i32, SCALE_FACTOR, SCALE_FACTOR));
+                    .fill_rect(Rect::new(x as i32, y as i32, scale, scale));
             }
         }
         self.canvas.present();
     }
 }
 
+// value has one bit per XO-CHIP plane
 fn color(value: u8) -> pixels::Color {
-    if value == 0 {
-        pixels::Color::RGB(150, 210, 150)
-    } else {
-        pixels::Color::RGB(30, 40, 30)
-    }
-}
\ No newline at end of file
+    let [r, g, b] = PALETTE[(value & 0b11) as usize];
+    pixels::Color::RGB(r, g, b)
+}
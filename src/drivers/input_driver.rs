@@ -1,61 +1,197 @@
 // taken from https://github.com/starrhorne/chip8-rust/blob/master/src/drivers/input_driver.rs
 
+use std::collections::HashMap;
+
 use sdl2;
+use sdl2::controller::{Button, GameController};
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::GameControllerSubsystem;
+use sdl2::keyboard::{Keycode, Mod, Scancode};
+
+use crate::keymap::Keymap;
+use crate::keypad::KeyEvent;
 
 pub struct InputDriver {
     events: sdl2::EventPump,
+    // keys pressed since the last poll that aren't bound to a CHIP-8 key, for frontend hotkeys
+    key_presses: Vec<(Keycode, Mod)>,
+    // host key to CHIP-8 key
+    bindings: HashMap<Keycode, usize>,
+    // None when SDL can't do game controllers
+    controller_subsystem: Option<GameControllerSubsystem>,
+    // connected gamepads by instance id
+    controllers: HashMap<i32, GameController>,
+    // gamepad button to CHIP-8 key
+    button_bindings: HashMap<Button, usize>,
+    // CHIP-8 key presses and releases since the last poll
+    key_events: Vec<KeyEvent>,
+    // how many host keys and buttons hold each CHIP-8 key down
+    held: [u8; 16],
+    // of the last SDL event seen
+    timestamp: u32,
 }
 
 impl InputDriver {
     pub fn new(sdl_context: &sdl2::Sdl) -> Self {
-        InputDriver { events: sdl_context.event_pump().unwrap() }
+        // SDL sends a device added event for every gamepad already plugged in, so they're opened in poll
+        let mut input_driver = InputDriver {
+            events: sdl_context.event_pump().unwrap(),
+            key_presses: Vec::new(),
+            bindings: HashMap::new(),
+            controller_subsystem: sdl_context.game_controller().ok(),
+            controllers: HashMap::new(),
+            button_bindings: HashMap::new(),
+            key_events: Vec::new(),
+            held: [0; 16],
+            timestamp: 0,
+        };
+        input_driver.set_keymap(&Keymap::default()).unwrap();
+        input_driver
+    }
+
+    // Err names the first host key or button SDL doesn't know
+    pub fn set_keymap(&mut self, keymap: &Keymap) -> Result<(), String> {
+        let mut bindings = HashMap::new();
+        for (key, name) in keymap.iter_keys() {
+            let keycode = Keycode::from_name(name).ok_or_else(|| format!("unknown host key '{}' bound to CHIP-8 key {:X}", name, key))?;
+            bindings.insert(keycode, key);
+        }
+
+        let mut button_bindings = HashMap::new();
+        for (key, name) in keymap.iter_buttons() {
+            let button = Button::from_string(name).ok_or_else(|| format!("unknown gamepad button '{}' bound to CHIP-8 key {:X}", name, key))?;
+            button_bindings.insert(button, key);
+        }
+
+        self.bindings = bindings;
+        self.button_bindings = button_bindings;
+        Ok(())
+    }
+
+
+    // Keys that went down during the last poll, key repeat is ignored. Keys the keymap binds are
+    // left out so a game key never doubles as a hotkey.
+    pub fn key_presses(&self) -> &[(Keycode, Mod)] {
+        &self.key_presses
     }
 
+    // CHIP-8 keys that went down or up during the last poll, in order
+    pub fn key_events(&self) -> &[KeyEvent] {
+        &self.key_events
+    }
+
+    // Whether a host key that the keymap doesn't bind is held down right now
+    pub fn is_held(&self, key: Keycode) -> bool {
+        !self.bindings.contains_key(&key) && Scancode::from_keycode(key).is_some_and(|scancode| self.events.keyboard_state().is_scancode_pressed(scancode))
+    }
 
+
+    // Err means the window was closed
+    #[allow(clippy::result_unit_err)]
     pub fn poll(&mut self) -> Result<[bool; 16], ()> {
 
-        for event in self.events.poll_iter() {
-            if let Event::Quit { .. } = event {
-                return Err(());
-            };
+        self.key_presses.clear();
+        self.key_events.clear();
+        let events: Vec<Event> = self.events.poll_iter().collect();
+        for event in events {
+            match event {
+                Event::Quit { .. } => return Err(()),
+                Event::KeyDown { keycode: Some(keycode), keymod, repeat: false, timestamp, .. } => {
+                    match self.bindings.get(&keycode) {
+                        Some(&key) => self.key_changed(key, true, timestamp),
+                        None => self.key_presses.push((keycode, keymod)),
+                    }
+                },
+                Event::KeyUp { keycode: Some(keycode), repeat: false, timestamp, .. } => {
+                    if let Some(&key) = self.bindings.get(&keycode) {
+                        self.key_changed(key, false, timestamp);
+                    }
+                },
+                Event::ControllerButtonDown { button, timestamp, .. } => {
+                    if let Some(&key) = self.button_bindings.get(&button) {
+                        self.key_changed(key, true, timestamp);
+                    }
+                },
+                Event::ControllerButtonUp { button, timestamp, .. } => {
+                    if let Some(&key) = self.button_bindings.get(&button) {
+                        self.key_changed(key, false, timestamp);
+                    }
+                },
+                // which is the device index here but the instance id once removed
+                Event::ControllerDeviceAdded { which, .. } => self.open_controller(which as u32),
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    if let Some(controller) = self.controllers.remove(&which) {
+                        println!("gamepad disconnected: {}", controller.name());
+                    }
+                },
+                _ => {},
+            }
         }
 
-        let keys: Vec<Keycode> = self.events
-            .keyboard_state()
-            .pressed_scancodes()
-            .filter_map(Keycode::from_scancode)
-            .collect();
-
         let mut chip8_keys = [false; 16];
 
-        for key in keys {
-            let index = match key {
-                Keycode::Num1 => Some(0x1),
-                Keycode::Num2 => Some(0x2),
-                Keycode::Num3 => Some(0x3),
-                Keycode::Num4 => Some(0xc),
-                Keycode::Q => Some(0x4),
-                Keycode::W => Some(0x5),
-                Keycode::E => Some(0x6),
-                Keycode::R => Some(0xd),
-                Keycode::A => Some(0x7),
-                Keycode::S => Some(0x8),
-                Keycode::D => Some(0x9),
-                Keycode::F => Some(0xe),
-                Keycode::Z => Some(0xa),
-                Keycode::X => Some(0x0),
-                Keycode::C => Some(0xb),
-                Keycode::V => Some(0xf),
-                _ => None,
-            };
-
-            if let Some(i) = index {
-                chip8_keys[i] = true;
+        for scancode in self.events.keyboard_state().pressed_scancodes() {
+            if let Some(&key) = Keycode::from_scancode(scancode).and_then(|keycode| self.bindings.get(&keycode)) {
+                chip8_keys[key] = true;
+            }
+        }
+
+        for controller in self.controllers.values() {
+            for (&button, &key) in &self.button_bindings {
+                if controller.button(button) {
+                    chip8_keys[key] = true;
+                }
+            }
+        }
+
+        // events can get lost, e.g. a gamepad unplugged with a button held or the window losing
+        // focus, so the events are brought in line with what is actually held
+        for (key, &down) in chip8_keys.iter().enumerate() {
+            if down != (self.held[key] > 0) {
+                self.held[key] = down as u8;
+                self.key_events.push(KeyEvent { key: key as u8, down, timestamp: self.timestamp });
             }
         }
 
         Ok(chip8_keys)
     }
+
+    // A host key or button bound to key went down or up, only the first press and the last
+    // release make an event when several are held at once
+    fn key_changed(&mut self, key: usize, down: bool, timestamp: u32) {
+        self.timestamp = timestamp;
+        let held = &mut self.held[key];
+        if down {
+            *held += 1;
+            if *held > 1 {
+                return;
+            }
+        }
+        else {
+            if *held == 0 {
+                return;
+            }
+            *held -= 1;
+            if *held > 0 {
+                return;
+            }
+        }
+
+        self.key_events.push(KeyEvent { key: key as u8, down, timestamp });
+    }
+
+    fn open_controller(&mut self, index: u32) {
+        let subsystem = match &self.controller_subsystem {
+            Some(subsystem) => subsystem,
+            None => return,
+        };
+
+        match subsystem.open(index) {
+            Ok(controller) => {
+                println!("gamepad connected: {}", controller.name());
+                self.controllers.insert(controller.instance_id(), controller);
+            },
+            Err(error) => eprintln!("can't open gamepad {}: {}", index, error),
+        }
+    }
 }
\ No newline at end of file
@@ -1,7 +1,15 @@
 mod cartridge_driver;
+#[cfg(feature = "sdl")]
 mod input_driver;
+#[cfg(feature = "sdl")]
 mod display_driver;
+#[cfg(feature = "sdl")]
+mod audio_driver;
 
 pub use self::cartridge_driver::CartridgeDriver;
+#[cfg(feature = "sdl")]
 pub use self::input_driver::InputDriver;
-pub use self::display_driver::DisplayDriver;
\ No newline at end of file
+#[cfg(feature = "sdl")]
+pub use self::display_driver::{DisplayDriver, DEFAULT_SCALE};
+#[cfg(feature = "sdl")]
+pub use self::audio_driver::{AudioDriver, Waveform, DEFAULT_FREQUENCY, DEFAULT_VOLUME};
@@ -0,0 +1,64 @@
+// Faults raised while executing a ROM
+use std::error::Error;
+use std::fmt;
+use crate::memory::OutOfBounds;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecErrorKind {
+    // the opcode doesn't decode to any known instruction
+    UnknownOpcode,
+    // 2NNN with the stack already at its maximum depth
+    StackOverflow,
+    // 00EE with nothing on the stack to return to
+    StackUnderflow,
+    // 0NNN: calls a machine code routine of the original hardware, which we can't run
+    SysCall,
+    // an I relative access past the end of RAM under MemoryPolicy::Fault
+    MemoryOutOfBounds(usize),
+}
+
+impl fmt::Display for ExecErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecErrorKind::UnknownOpcode => f.write_str("unknown opcode"),
+            ExecErrorKind::StackOverflow => f.write_str("call with a full stack"),
+            ExecErrorKind::StackUnderflow => f.write_str("return with an empty stack"),
+            ExecErrorKind::SysCall => f.write_str("unsupported machine code call"),
+            ExecErrorKind::MemoryOutOfBounds(address) => write!(f, "memory access out of bounds at {:X}", address),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExecError {
+    // address of the faulting instruction
+    pub pc: u16,
+    pub opcode: u16,
+    pub kind: ExecErrorKind,
+}
+
+impl From<OutOfBounds> for ExecErrorKind {
+    fn from(error: OutOfBounds) -> Self {
+        ExecErrorKind::MemoryOutOfBounds(error.address)
+    }
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {:03X} (opcode {:04X})", self.kind, self.pc, self.opcode)
+    }
+}
+
+impl Error for ExecError {}
+
+// What the processor does when an instruction faults
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    // stop executing, every following cycle returns the same error
+    Halt,
+    // skip the instruction silently
+    Ignore,
+    // print the error to stderr and skip the instruction
+    #[default]
+    Log,
+}
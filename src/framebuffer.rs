@@ -0,0 +1,138 @@
+// The screen the processor draws to. Its resolution can change at runtime (SUPER-CHIP lores/hires)
+// and every pixel holds one bit per bitplane (XO-CHIP), so a pixel value is 0-3.
+use std::fmt;
+
+pub const PLANE_COUNT: usize = 2;
+// bitmask with every plane set
+pub const ALL_PLANES: u8 = 0b11;
+
+// RGB for each pixel value, used by the window and by image dumps
+pub const PALETTE: [[u8; 3]; 4] = [
+    [150, 210, 150],
+    [30, 40, 30],
+    [90, 130, 90],
+    [60, 85, 60],
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    // row major, width * height pixels
+    pixels: Vec<u8>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0u8; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn resolution(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    // Switch resolution, the screen is cleared
+    pub fn set_resolution(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.pixels = vec![0u8; width * height];
+    }
+
+    // Plane bits of the pixel at (x, y)
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, value: u8) {
+        self.pixels[y * self.width + x] = value & ALL_PLANES;
+    }
+
+    // Whether the pixel is on in any plane
+    pub fn is_lit(&self, x: usize, y: usize) -> bool {
+        self.pixel(x, y) != 0
+    }
+
+    // Whether the pixel is on in plane (0 or 1)
+    pub fn plane_pixel(&self, plane: usize, x: usize, y: usize) -> bool {
+        self.pixel(x, y) & (1 << plane) != 0
+    }
+
+    // XOR the pixel in the given planes, returns true when a lit pixel was turned off
+    pub fn toggle(&mut self, x: usize, y: usize, planes: u8) -> bool {
+        let pixel = &mut self.pixels[y * self.width + x];
+        let collision = *pixel & planes != 0;
+        *pixel ^= planes;
+        collision
+    }
+
+    // Clear the given planes only
+    pub fn clear(&mut self, planes: u8) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel &= !planes;
+        }
+    }
+
+    // Move the given planes by (dx, dy) pixels, whatever scrolls in is blank
+    pub fn scroll(&mut self, dx: isize, dy: isize, planes: u8) {
+        let previous = self.pixels.clone();
+        let (width, height) = (self.width as isize, self.height as isize);
+
+        for y in 0..height {
+            for x in 0..width {
+                let (source_x, source_y) = (x - dx, y - dy);
+
+                let moved = if source_x >= 0 && source_x < width && source_y >= 0 && source_y < height {
+                    previous[(source_y * width + source_x) as usize] & planes
+                }
+                else {
+                    0
+                };
+
+                let index = (y * width + x) as usize;
+                self.pixels[index] = (previous[index] & !planes) | moved;
+            }
+        }
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.pixels.chunks(self.width)
+    }
+
+    // Every pixel as (x, y, plane bits), row by row
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, u8)> + '_ {
+        let width = self.width;
+        self.pixels.iter().enumerate().map(move |(i, &pixel)| (i % width, i / width, pixel))
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.pixels
+    }
+}
+
+// One character per pixel: _ off, * plane 1, + plane 2, # both
+impl fmt::Display for FrameBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.rows() {
+            let line: String = row.iter().map(|&pixel| match pixel {
+                0 => '_',
+                1 => '*',
+                2 => '+',
+                _ => '#',
+            }).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
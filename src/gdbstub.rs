@@ -0,0 +1,460 @@
+// GDB remote serial protocol stub, so gdb or lldb can attach over TCP:
+//   (gdb) target remote localhost:<port>
+// GDB has no CHIP-8 architecture, so the registers are described in a target description that
+// the client fetches with qXfer: V0-VF (8 bits), I, PC and SP (16 bits, little endian, SP is the
+// stack depth). Supports register and memory access, software breakpoints and single step.
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use crate::error::{ExecError, ExecErrorKind};
+use crate::processor::Processor;
+
+// GDB register numbers after V0-VF
+const REGISTER_I: usize = 16;
+const REGISTER_PC: usize = 17;
+const REGISTER_SP: usize = 18;
+const REGISTER_COUNT: usize = 19;
+
+// Signals reported in stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGABRT: u8 = 6;
+const SIGSEGV: u8 = 11;
+
+// The connection to the client, a TcpStream outside of tests
+pub trait Connection: Read + Write {
+    // Make reads return WouldBlock instead of waiting when there's nothing to read
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+impl Connection for TcpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+// What the client did with the session, returned from every frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Session {
+    Attached,
+    // the client detached or disconnected, the ROM should carry on without it
+    Detached,
+    // the client killed the program, or it exited by itself
+    Ended,
+}
+
+// What a packet asked the stopped target to do
+enum Resume {
+    Stay,
+    Continue,
+    Step,
+    Leave(Session),
+}
+
+pub struct GdbStub<C: Connection = TcpStream> {
+    stream: C,
+    // bytes read while checking for ^C, read_byte hands them out before reading the stream again
+    pending: VecDeque<u8>,
+    breakpoints: BTreeSet<u16>,
+    stopped: bool,
+    // stop after the next instruction
+    stepping: bool,
+    // QStartNoAckMode: the client stopped sending and expecting +/- acknowledgements
+    no_ack: bool,
+}
+
+impl GdbStub {
+    // Wait on 127.0.0.1:port until a client connects, the target starts out stopped
+    pub fn listen(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let (stream, _) = listener.accept()?;
+        Self::new(stream)
+    }
+
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        Ok(Self::with_connection(stream))
+    }
+}
+
+impl<C: Connection> GdbStub<C> {
+    // Serve a client that is already connected, the target starts out stopped
+    pub fn with_connection(stream: C) -> Self {
+        Self {
+            stream,
+            pending: VecDeque::new(),
+            breakpoints: BTreeSet::new(),
+            stopped: true,
+            stepping: false,
+            no_ack: false,
+        }
+    }
+
+    // Run one frame like Processor::run_frame, serving the client whenever the target is stopped
+    pub fn run_frame(&mut self, processor: &mut Processor, keypad: [bool; 16]) -> io::Result<Session> {
+        if !self.stopped && self.interrupted()? {
+            self.stop(SIGINT)?;
+        }
+
+        for _ in 0..processor.instructions_for_next_frame() {
+            if !self.stopped && self.breakpoints.contains(&processor.pc()) {
+                self.stop(SIGTRAP)?;
+            }
+
+            while self.stopped {
+                match self.serve(processor)? {
+                    Resume::Stay => {},
+                    Resume::Continue => self.stopped = false,
+                    Resume::Step => {
+                        self.stopped = false;
+                        self.stepping = true;
+                    },
+                    Resume::Leave(session) => return Ok(session),
+                }
+            }
+
+            // continuing after a fault runs the faulting instruction again
+            if processor.halted().is_some() {
+                processor.resume();
+            }
+
+            if let Err(error) = processor.cycle(keypad) {
+                self.stop(signal(error))?;
+                continue;
+            }
+
+            if processor.exited() {
+                self.send(b"W00")?;
+                return Ok(Session::Ended);
+            }
+
+            if self.stepping {
+                self.stepping = false;
+                self.stop(SIGTRAP)?;
+            }
+        }
+        processor.tick_timers();
+
+        Ok(Session::Attached)
+    }
+
+    fn stop(&mut self, signal: u8) -> io::Result<()> {
+        self.stopped = true;
+        self.stepping = false;
+        self.send(format!("S{:02x}", signal).as_bytes())
+    }
+
+    // Check for the ^C byte the client sends to interrupt a running target, without blocking.
+    // Anything else that arrived is kept for the next packet.
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let mut buffer = [0u8; 256];
+        let result = self.stream.read(&mut buffer);
+        self.stream.set_nonblocking(false)?;
+
+        let read = match result {
+            Ok(0) => return Err(io::Error::from(ErrorKind::UnexpectedEof)),
+            Ok(read) => read,
+            Err(error) if error.kind() == ErrorKind::WouldBlock => return Ok(false),
+            Err(error) => return Err(error),
+        };
+
+        let bytes = &buffer[..read];
+        self.pending.extend(bytes.iter().filter(|&&byte| byte != 0x03));
+        Ok(bytes.contains(&0x03))
+    }
+
+    // Read one packet and answer it
+    fn serve(&mut self, processor: &mut Processor) -> io::Result<Resume> {
+        let packet = match self.receive() {
+            Ok(Some(packet)) => packet,
+            // ^C while already stopped
+            Ok(None) => return Ok(Resume::Stay),
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(Resume::Leave(Session::Detached)),
+            Err(error) => return Err(error),
+        };
+        let packet = String::from_utf8_lossy(&packet).into_owned();
+
+        let (reply, resume) = self.handle(&packet, processor);
+        if let Some(reply) = reply {
+            self.send(reply.as_bytes())?;
+        }
+        Ok(resume)
+    }
+
+    fn handle(&mut self, packet: &str, processor: &mut Processor) -> (Option<String>, Resume) {
+        let reply = |text: &str| (Some(text.to_string()), Resume::Stay);
+        let (command, args) = packet.split_at(packet.len().min(1));
+
+        match command {
+            "?" => reply(&format!("S{:02x}", SIGTRAP)),
+            "g" => reply(&(0..REGISTER_COUNT).map(|n| read_register(processor, n)).collect::<String>()),
+            "G" => {
+                let mut rest = args;
+                for n in 0..REGISTER_COUNT {
+                    let width = register_size(n) * 2;
+                    if rest.len() < width {
+                        return reply("E01");
+                    }
+                    let (value, tail) = rest.split_at(width);
+                    if write_register(processor, n, value).is_none() {
+                        return reply("E01");
+                    }
+                    rest = tail;
+                }
+                reply("OK")
+            },
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(n) if n < REGISTER_COUNT => reply(&read_register(processor, n)),
+                _ => reply("E01"),
+            },
+            "P" => {
+                let written = args.split_once('=').and_then(|(n, value)| {
+                    let n = usize::from_str_radix(n, 16).ok().filter(|&n| n < REGISTER_COUNT)?;
+                    write_register(processor, n, value)
+                });
+                reply(if written.is_some() { "OK" } else { "E01" })
+            },
+            "m" => match parse_range(args) {
+                Some((address, length)) => {
+                    let mut bytes = String::new();
+                    for offset in 0..length {
+                        match processor.memory().peek(address + offset) {
+                            Ok(byte) => bytes += &format!("{:02x}", byte),
+                            Err(_) => break,
+                        }
+                    }
+                    if bytes.is_empty() && length > 0 { reply("E01") } else { reply(&bytes) }
+                },
+                None => reply("E01"),
+            },
+            "M" => {
+                let written = args.split_once(':').and_then(|(range, data)| {
+                    let (address, length) = parse_range(range)?;
+                    let bytes = decode_hex(data)?;
+                    if bytes.len() != length {
+                        return None;
+                    }
+                    let memory = processor.memory_mut();
+                    for (offset, byte) in bytes.into_iter().enumerate() {
+                        let address = memory.resolve(address + offset).ok()?;
+                        memory.as_mut_slice()[address] = byte;
+                    }
+                    Some(())
+                });
+                reply(if written.is_some() { "OK" } else { "E01" })
+            },
+            // software and hardware breakpoints are the same thing here
+            "Z" | "z" => {
+                let mut fields = args.split(',');
+                let kind = fields.next();
+                let address = fields.next().and_then(|address| u16::from_str_radix(address, 16).ok());
+                match (kind, address) {
+                    (Some("0" | "1"), Some(address)) => {
+                        if command == "Z" {
+                            self.breakpoints.insert(address);
+                        }
+                        else {
+                            self.breakpoints.remove(&address);
+                        }
+                        reply("OK")
+                    },
+                    // watchpoints aren't supported over the stub
+                    _ => reply(""),
+                }
+            },
+            "c" | "s" => {
+                // an optional address to resume from
+                if let Ok(address) = u16::from_str_radix(args, 16) {
+                    processor.set_pc(address);
+                }
+                (None, if command == "c" { Resume::Continue } else { Resume::Step })
+            },
+            "D" => (Some(String::from("OK")), Resume::Leave(Session::Detached)),
+            "k" => (None, Resume::Leave(Session::Ended)),
+            "H" => reply("OK"),
+            "T" => reply("OK"),
+            "q" | "Q" => self.handle_query(packet),
+            // vCont and anything else we don't know gets an empty reply, gdb falls back to c and s
+            _ => reply(""),
+        }
+    }
+
+    fn handle_query(&mut self, packet: &str) -> (Option<String>, Resume) {
+        let reply = |text: &str| (Some(text.to_string()), Resume::Stay);
+
+        if packet.starts_with("qSupported") {
+            return reply("PacketSize=1000;qXfer:features:read+;QStartNoAckMode+;swbreak+");
+        }
+        if packet == "QStartNoAckMode" {
+            // the OK is still acknowledged, the mode starts after it
+            let response = reply("OK");
+            self.no_ack = true;
+            return response;
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let description = target_description();
+            return match parse_range(range) {
+                Some((offset, length)) if offset <= description.len() => {
+                    let end = description.len().min(offset + length);
+                    let prefix = if end == description.len() { "l" } else { "m" };
+                    reply(&format!("{}{}", prefix, &description[offset..end]))
+                },
+                _ => reply("E01"),
+            };
+        }
+
+        match packet {
+            "qAttached" => reply("1"),
+            "qC" => reply("QC1"),
+            "qfThreadInfo" => reply("m1"),
+            "qsThreadInfo" => reply("l"),
+            _ => reply(""),
+        }
+    }
+
+    // Next packet from the client without the framing, None for a ^C
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            match self.read_byte()? {
+                b'$' => break,
+                0x03 => return Ok(None),
+                // acknowledgements and noise between packets
+                _ => {},
+            }
+        }
+
+        let mut packet = Vec::new();
+        let mut checksum = 0u8;
+        loop {
+            let byte = self.read_byte()?;
+            if byte == b'#' {
+                break;
+            }
+            checksum = checksum.wrapping_add(byte);
+            // } escapes the next byte in binary data
+            if byte == b'}' {
+                let escaped = self.read_byte()?;
+                checksum = checksum.wrapping_add(escaped);
+                packet.push(escaped ^ 0x20);
+            }
+            else {
+                packet.push(byte);
+            }
+        }
+
+        let digits = [self.read_byte()?, self.read_byte()?];
+        let expected = std::str::from_utf8(&digits).ok().and_then(|digits| u8::from_str_radix(digits, 16).ok());
+
+        if !self.no_ack {
+            if expected != Some(checksum) {
+                self.stream.write_all(b"-")?;
+                return self.receive();
+            }
+            self.stream.write_all(b"+")?;
+        }
+
+        Ok(Some(packet))
+    }
+
+    fn read_byte(&mut self) -> io::Result<u8> {
+        if let Some(byte) = self.pending.pop_front() {
+            return Ok(byte);
+        }
+        let mut byte = [0u8; 1];
+        self.stream.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        let checksum = data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        packet.extend_from_slice(data);
+        packet.extend_from_slice(format!("#{:02x}", checksum).as_bytes());
+        self.stream.write_all(&packet)?;
+
+        // wait for the client to acknowledge, resending on a -
+        while !self.no_ack {
+            match self.read_byte()? {
+                b'+' => break,
+                b'-' => self.stream.write_all(&packet)?,
+                _ => {},
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn signal(error: ExecError) -> u8 {
+    match error.kind {
+        ExecErrorKind::UnknownOpcode | ExecErrorKind::SysCall => SIGILL,
+        ExecErrorKind::MemoryOutOfBounds(_) => SIGSEGV,
+        ExecErrorKind::StackOverflow | ExecErrorKind::StackUnderflow => SIGABRT,
+    }
+}
+
+fn register_size(n: usize) -> usize {
+    match n {
+        REGISTER_I | REGISTER_PC | REGISTER_SP => 2,
+        _ => 1,
+    }
+}
+
+// Register value as little endian hex
+fn read_register(processor: &Processor, n: usize) -> String {
+    match n {
+        REGISTER_I => format!("{:04x}", processor.index().swap_bytes()),
+        REGISTER_PC => format!("{:04x}", processor.pc().swap_bytes()),
+        // an unlimited stack can be deeper than the field, it shows as full
+        REGISTER_SP => format!("{:04x}", (processor.stack().len().min(u16::MAX as usize) as u16).swap_bytes()),
+        _ => format!("{:02x}", processor.registers()[n]),
+    }
+}
+
+fn write_register(processor: &mut Processor, n: usize, hex: &str) -> Option<()> {
+    let bytes = decode_hex(hex)?;
+    let value = match bytes.as_slice() {
+        [low] => *low as u16,
+        [low, high] => (*high as u16) << 8 | *low as u16,
+        _ => return None,
+    };
+
+    match n {
+        REGISTER_I => processor.set_index(value),
+        REGISTER_PC => processor.set_pc(value),
+        REGISTER_SP => processor.set_stack_pointer(value as usize),
+        _ => processor.set_register(n, value as u8),
+    }
+    Some(())
+}
+
+// addr,length in hex
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (address, length) = text.split_once(',')?;
+    Some((usize::from_str_radix(address, 16).ok()?, usize::from_str_radix(length, 16).ok()?))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+fn target_description() -> String {
+    let mut registers = String::new();
+    for x in 0..16 {
+        registers += &format!("    <reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\" regnum=\"{}\"/>\n", x, x);
+    }
+    registers += &format!("    <reg name=\"i\" bitsize=\"16\" type=\"data_ptr\" regnum=\"{}\"/>\n", REGISTER_I);
+    registers += &format!("    <reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\" regnum=\"{}\"/>\n", REGISTER_PC);
+    registers += &format!("    <reg name=\"sp\" bitsize=\"16\" type=\"uint16\" regnum=\"{}\"/>\n", REGISTER_SP);
+
+    format!("<?xml version=\"1.0\"?>\n\
+        <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n\
+        <target version=\"1.0\">\n  <feature name=\"org.chip8.core\">\n{}  </feature>\n</target>\n", registers)
+}
@@ -0,0 +1,384 @@
+// Decoded opcodes, shared by the processor, the disassembler and anything else that needs to read a ROM.
+// Mnemonics follow Cowgod's technical reference http://devernay.free.fr/hacks/chip8/C8TECH10.HTM
+// with the SUPER-CHIP and XO-CHIP additions named the way most assemblers do.
+use std::fmt;
+use crate::quirks::InstructionSet;
+
+// x and y are register numbers, nnn an address, nn a byte and n a nibble
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    // 00E0
+    Clear,
+    // 00EE
+    Return,
+    // 0nnn
+    Sys(u16),
+    // 00Cn (SUPER-CHIP)
+    ScrollDown(u8),
+    // 00Dn (XO-CHIP)
+    ScrollUp(u8),
+    // 00FB (SUPER-CHIP)
+    ScrollRight,
+    // 00FC (SUPER-CHIP)
+    ScrollLeft,
+    // 00FD (SUPER-CHIP)
+    Exit,
+    // 00FE (SUPER-CHIP)
+    LowRes,
+    // 00FF (SUPER-CHIP)
+    HighRes,
+    // 1nnn
+    Jump(u16),
+    // 2nnn
+    Call(u16),
+    // 3xnn
+    SkipIfEqual(u8, u8),
+    // 4xnn
+    SkipIfNotEqual(u8, u8),
+    // 5xy0
+    SkipIfRegistersEqual(u8, u8),
+    // 5xy2 (XO-CHIP)
+    SaveRange(u8, u8),
+    // 5xy3 (XO-CHIP)
+    LoadRange(u8, u8),
+    // 6xnn
+    Load(u8, u8),
+    // 7xnn
+    Add(u8, u8),
+    // 8xy0
+    Move(u8, u8),
+    // 8xy1
+    Or(u8, u8),
+    // 8xy2
+    And(u8, u8),
+    // 8xy3
+    Xor(u8, u8),
+    // 8xy4
+    AddRegisters(u8, u8),
+    // 8xy5
+    Sub(u8, u8),
+    // 8xy6
+    ShiftRight(u8, u8),
+    // 8xy7
+    SubReverse(u8, u8),
+    // 8xyE
+    ShiftLeft(u8, u8),
+    // 9xy0
+    SkipIfRegistersNotEqual(u8, u8),
+    // Annn
+    LoadIndex(u16),
+    // Bnnn
+    JumpOffset(u16),
+    // Cxnn
+    Random(u8, u8),
+    // Dxyn
+    Draw(u8, u8, u8),
+    // Ex9E
+    SkipIfKey(u8),
+    // ExA1
+    SkipIfNotKey(u8),
+    // F000 nnnn (XO-CHIP), the address is the word following the opcode
+    LoadIndexLong(u16),
+    // Fn01 (XO-CHIP)
+    Plane(u8),
+    // F002 (XO-CHIP)
+    Audio,
+    // Fx07
+    LoadDelay(u8),
+    // Fx0A
+    WaitKey(u8),
+    // Fx15
+    SetDelay(u8),
+    // Fx18
+    SetSound(u8),
+    // Fx1E
+    AddIndex(u8),
+    // Fx29
+    LoadFont(u8),
+    // Fx30 (SUPER-CHIP)
+    LoadBigFont(u8),
+    // Fx33
+    StoreBcd(u8),
+    // Fx3A (XO-CHIP)
+    Pitch(u8),
+    // Fx55
+    StoreRegisters(u8),
+    // Fx65
+    LoadRegisters(u8),
+    // Fx75 (SUPER-CHIP)
+    StoreFlags(u8),
+    // Fx85 (SUPER-CHIP)
+    LoadFlags(u8),
+    // anything else
+    Unknown(u16),
+}
+
+impl Instruction {
+    // Decode every instruction this emulator knows about, regardless of platform.
+    // next is the word after the opcode, only F000 nnnn uses it
+    pub fn decode(opcode: u16, next: u16) -> Self {
+        // just put all the nibbles in a tuple
+        let nibbles = (
+            ((opcode & 0xF000) >> 12) as u8,
+            ((opcode & 0x0F00) >> 8) as u8,
+            ((opcode & 0x00F0) >> 4) as u8,
+            (opcode & 0x000F) as u8
+            );
+
+        //_*** First nibble of opcode
+        //*X**
+        //**Y*
+        //***N
+        let (_, x, y, n) = nibbles;
+
+        //*NNN
+        let nnn = opcode & 0x0FFF;
+
+        //**NN
+        let nn = (opcode & 0x00FF) as u8;
+
+        match nibbles {
+            (0x0, 0x0, 0xE, 0x0) => Instruction::Clear,
+            (0x0, 0x0, 0xE, 0xE) => Instruction::Return,
+            (0x0, 0x0, 0xC, _) => Instruction::ScrollDown(n),
+            (0x0, 0x0, 0xD, _) => Instruction::ScrollUp(n),
+            (0x0, 0x0, 0xF, 0xB) => Instruction::ScrollRight,
+            (0x0, 0x0, 0xF, 0xC) => Instruction::ScrollLeft,
+            (0x0, 0x0, 0xF, 0xD) => Instruction::Exit,
+            (0x0, 0x0, 0xF, 0xE) => Instruction::LowRes,
+            (0x0, 0x0, 0xF, 0xF) => Instruction::HighRes,
+            (0x0, _, _, _) => Instruction::Sys(nnn),
+            (0x1, _, _, _) => Instruction::Jump(nnn),
+            (0x2, _, _, _) => Instruction::Call(nnn),
+            (0x3, _, _, _) => Instruction::SkipIfEqual(x, nn),
+            (0x4, _, _, _) => Instruction::SkipIfNotEqual(x, nn),
+            (0x5, _, _, 0x0) => Instruction::SkipIfRegistersEqual(x, y),
+            (0x5, _, _, 0x2) => Instruction::SaveRange(x, y),
+            (0x5, _, _, 0x3) => Instruction::LoadRange(x, y),
+            (0x6, _, _, _) => Instruction::Load(x, nn),
+            (0x7, _, _, _) => Instruction::Add(x, nn),
+            (0x8, _, _, 0x0) => Instruction::Move(x, y),
+            (0x8, _, _, 0x1) => Instruction::Or(x, y),
+            (0x8, _, _, 0x2) => Instruction::And(x, y),
+            (0x8, _, _, 0x3) => Instruction::Xor(x, y),
+            (0x8, _, _, 0x4) => Instruction::AddRegisters(x, y),
+            (0x8, _, _, 0x5) => Instruction::Sub(x, y),
+            (0x8, _, _, 0x6) => Instruction::ShiftRight(x, y),
+            (0x8, _, _, 0x7) => Instruction::SubReverse(x, y),
+            (0x8, _, _, 0xE) => Instruction::ShiftLeft(x, y),
+            (0x9, _, _, 0x0) => Instruction::SkipIfRegistersNotEqual(x, y),
+            (0xA, _, _, _) => Instruction::LoadIndex(nnn),
+            (0xB, _, _, _) => Instruction::JumpOffset(nnn),
+            (0xC, _, _, _) => Instruction::Random(x, nn),
+            (0xD, _, _, _) => Instruction::Draw(x, y, n),
+            (0xE, _, 0x9, 0xE) => Instruction::SkipIfKey(x),
+            (0xE, _, 0xA, 0x1) => Instruction::SkipIfNotKey(x),
+            (0xF, 0x0, 0x0, 0x0) => Instruction::LoadIndexLong(next),
+            (0xF, _, 0x0, 0x1) => Instruction::Plane(x),
+            (0xF, 0x0, 0x0, 0x2) => Instruction::Audio,
+            (0xF, _, 0x0, 0x7) => Instruction::LoadDelay(x),
+            (0xF, _, 0x0, 0xA) => Instruction::WaitKey(x),
+            (0xF, _, 0x1, 0x5) => Instruction::SetDelay(x),
+            (0xF, _, 0x1, 0x8) => Instruction::SetSound(x),
+            (0xF, _, 0x1, 0xE) => Instruction::AddIndex(x),
+            (0xF, _, 0x2, 0x9) => Instruction::LoadFont(x),
+            (0xF, _, 0x3, 0x0) => Instruction::LoadBigFont(x),
+            (0xF, _, 0x3, 0x3) => Instruction::StoreBcd(x),
+            (0xF, _, 0x3, 0xA) => Instruction::Pitch(x),
+            (0xF, _, 0x5, 0x5) => Instruction::StoreRegisters(x),
+            (0xF, _, 0x6, 0x5) => Instruction::LoadRegisters(x),
+            (0xF, _, 0x7, 0x5) => Instruction::StoreFlags(x),
+            (0xF, _, 0x8, 0x5) => Instruction::LoadFlags(x),
+            _ => Instruction::Unknown(opcode),
+        }
+    }
+
+    // Decode the way an interpreter for the given instruction set would see it:
+    // extensions it doesn't know are machine code calls (0nnn) or unknown opcodes
+    pub fn decode_for(opcode: u16, next: u16, instruction_set: InstructionSet) -> Self {
+        let instruction = Self::decode(opcode, next);
+
+        if instruction.instruction_set() <= instruction_set {
+            instruction
+        }
+        else if opcode & 0xF000 == 0 {
+            Instruction::Sys(opcode & 0x0FFF)
+        }
+        else {
+            Instruction::Unknown(opcode)
+        }
+    }
+
+    // The first instruction set that has this instruction
+    pub fn instruction_set(&self) -> InstructionSet {
+        match self {
+            Instruction::ScrollDown(_)
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::Exit
+            | Instruction::LowRes
+            | Instruction::HighRes
+            | Instruction::LoadBigFont(_)
+            | Instruction::StoreFlags(_)
+            | Instruction::LoadFlags(_) => InstructionSet::SuperChip,
+            // Dxy0 draws nothing on the original CHIP-8 and a 16x16 sprite from SUPER-CHIP on,
+            // both read the same so it decodes the same everywhere
+            Instruction::ScrollUp(_)
+            | Instruction::SaveRange(_, _)
+            | Instruction::LoadRange(_, _)
+            | Instruction::LoadIndexLong(_)
+            | Instruction::Plane(_)
+            | Instruction::Audio
+            | Instruction::Pitch(_) => InstructionSet::XoChip,
+            _ => InstructionSet::Chip8,
+        }
+    }
+
+    // Encode back into the opcode, the inverse of decode. F000 nnnn's address is in second_word
+    pub fn opcode(&self) -> u16 {
+        let xy = |op: u16, x: u8, y: u8, n: u16| op | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | n;
+        let xnn = |op: u16, x: u8, nn: u8| op | (x as u16 & 0xF) << 8 | nn as u16;
+        let x = |op: u16, x: u8| op | (x as u16 & 0xF) << 8;
+
+        match *self {
+            Instruction::Clear => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::Sys(nnn) => nnn & 0x0FFF,
+            Instruction::ScrollDown(n) => 0x00C0 | (n as u16 & 0xF),
+            Instruction::ScrollUp(n) => 0x00D0 | (n as u16 & 0xF),
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::LowRes => 0x00FE,
+            Instruction::HighRes => 0x00FF,
+            Instruction::Jump(nnn) => 0x1000 | (nnn & 0x0FFF),
+            Instruction::Call(nnn) => 0x2000 | (nnn & 0x0FFF),
+            Instruction::SkipIfEqual(vx, nn) => xnn(0x3000, vx, nn),
+            Instruction::SkipIfNotEqual(vx, nn) => xnn(0x4000, vx, nn),
+            Instruction::SkipIfRegistersEqual(vx, vy) => xy(0x5000, vx, vy, 0x0),
+            Instruction::SaveRange(vx, vy) => xy(0x5000, vx, vy, 0x2),
+            Instruction::LoadRange(vx, vy) => xy(0x5000, vx, vy, 0x3),
+            Instruction::Load(vx, nn) => xnn(0x6000, vx, nn),
+            Instruction::Add(vx, nn) => xnn(0x7000, vx, nn),
+            Instruction::Move(vx, vy) => xy(0x8000, vx, vy, 0x0),
+            Instruction::Or(vx, vy) => xy(0x8000, vx, vy, 0x1),
+            Instruction::And(vx, vy) => xy(0x8000, vx, vy, 0x2),
+            Instruction::Xor(vx, vy) => xy(0x8000, vx, vy, 0x3),
+            Instruction::AddRegisters(vx, vy) => xy(0x8000, vx, vy, 0x4),
+            Instruction::Sub(vx, vy) => xy(0x8000, vx, vy, 0x5),
+            Instruction::ShiftRight(vx, vy) => xy(0x8000, vx, vy, 0x6),
+            Instruction::SubReverse(vx, vy) => xy(0x8000, vx, vy, 0x7),
+            Instruction::ShiftLeft(vx, vy) => xy(0x8000, vx, vy, 0xE),
+            Instruction::SkipIfRegistersNotEqual(vx, vy) => xy(0x9000, vx, vy, 0x0),
+            Instruction::LoadIndex(nnn) => 0xA000 | (nnn & 0x0FFF),
+            Instruction::JumpOffset(nnn) => 0xB000 | (nnn & 0x0FFF),
+            Instruction::Random(vx, nn) => xnn(0xC000, vx, nn),
+            Instruction::Draw(vx, vy, n) => xy(0xD000, vx, vy, n as u16 & 0xF),
+            Instruction::SkipIfKey(vx) => x(0xE09E, vx),
+            Instruction::SkipIfNotKey(vx) => x(0xE0A1, vx),
+            Instruction::LoadIndexLong(_) => 0xF000,
+            Instruction::Plane(n) => x(0xF001, n),
+            Instruction::Audio => 0xF002,
+            Instruction::LoadDelay(vx) => x(0xF007, vx),
+            Instruction::WaitKey(vx) => x(0xF00A, vx),
+            Instruction::SetDelay(vx) => x(0xF015, vx),
+            Instruction::SetSound(vx) => x(0xF018, vx),
+            Instruction::AddIndex(vx) => x(0xF01E, vx),
+            Instruction::LoadFont(vx) => x(0xF029, vx),
+            Instruction::LoadBigFont(vx) => x(0xF030, vx),
+            Instruction::StoreBcd(vx) => x(0xF033, vx),
+            Instruction::Pitch(vx) => x(0xF03A, vx),
+            Instruction::StoreRegisters(vx) => x(0xF055, vx),
+            Instruction::LoadRegisters(vx) => x(0xF065, vx),
+            Instruction::StoreFlags(vx) => x(0xF075, vx),
+            Instruction::LoadFlags(vx) => x(0xF085, vx),
+            Instruction::Unknown(opcode) => opcode,
+        }
+    }
+
+    // The word after the opcode for instructions that have one
+    pub fn second_word(&self) -> Option<u16> {
+        match *self {
+            Instruction::LoadIndexLong(nnnn) => Some(nnnn),
+            _ => None,
+        }
+    }
+
+    // Size in bytes, F000 nnnn carries its address in a second word
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::LoadIndexLong(_) => 4,
+            _ => 2,
+        }
+    }
+
+    // Instructions that conditionally skip the one after them
+    pub fn is_skip(&self) -> bool {
+        matches!(self,
+            Instruction::SkipIfEqual(_, _)
+            | Instruction::SkipIfNotEqual(_, _)
+            | Instruction::SkipIfRegistersEqual(_, _)
+            | Instruction::SkipIfRegistersNotEqual(_, _)
+            | Instruction::SkipIfKey(_)
+            | Instruction::SkipIfNotKey(_))
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Clear => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::Sys(nnn) => write!(f, "SYS 0x{:03X}", nnn),
+            Instruction::ScrollDown(n) => write!(f, "SCD {}", n),
+            Instruction::ScrollUp(n) => write!(f, "SCU {}", n),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::LowRes => write!(f, "LOW"),
+            Instruction::HighRes => write!(f, "HIGH"),
+            Instruction::Jump(nnn) => write!(f, "JP 0x{:03X}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL 0x{:03X}", nnn),
+            Instruction::SkipIfEqual(x, nn) => write!(f, "SE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipIfNotEqual(x, nn) => write!(f, "SNE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipIfRegistersEqual(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange(x, y) => write!(f, "SAVE V{:X} - V{:X}", x, y),
+            Instruction::LoadRange(x, y) => write!(f, "LOAD V{:X} - V{:X}", x, y),
+            Instruction::Load(x, nn) => write!(f, "LD V{:X}, 0x{:02X}", x, nn),
+            Instruction::Add(x, nn) => write!(f, "ADD V{:X}, 0x{:02X}", x, nn),
+            Instruction::Move(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddRegisters(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubReverse(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipIfRegistersNotEqual(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadIndex(nnn) => write!(f, "LD I, 0x{:03X}", nnn),
+            Instruction::JumpOffset(nnn) => write!(f, "JP V0, 0x{:03X}", nnn),
+            Instruction::Random(x, nn) => write!(f, "RND V{:X}, 0x{:02X}", x, nn),
+            Instruction::Draw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipIfKey(x) => write!(f, "SKP V{:X}", x),
+            Instruction::SkipIfNotKey(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::LoadIndexLong(nnnn) => write!(f, "LD I, 0x{:04X}", nnnn),
+            Instruction::Plane(n) => write!(f, "PLANE {}", n),
+            Instruction::Audio => write!(f, "AUDIO"),
+            Instruction::LoadDelay(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddIndex(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::LoadFont(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::LoadBigFont(x) => write!(f, "LD HF, V{:X}", x),
+            Instruction::StoreBcd(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::Pitch(x) => write!(f, "PITCH V{:X}", x),
+            Instruction::StoreRegisters(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::LoadRegisters(x) => write!(f, "LD V{:X}, [I]", x),
+            Instruction::StoreFlags(x) => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+            Instruction::Unknown(opcode) => write!(f, "DW 0x{:04X}", opcode),
+        }
+    }
+}
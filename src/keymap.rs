@@ -0,0 +1,364 @@
+// Which host keys press which CHIP-8 keys. Keymaps start from a preset and can be changed from a
+// TOML file like this one:
+//   # the layout to start from: qwerty, azerty, dvorak or numpad (default qwerty)
+//   preset = "qwerty"
+//   # CHIP-8 key (hex) = host key, or a list of them
+//   5 = ["W", "Up"]
+//
+//   # gamepad buttons, the same way
+//   [gamepad]
+//   5 = ["a", "rightshoulder"]
+//
+//   # only used when the ROM file is called TANK, applied on top of the above
+//   [roms.TANK]
+//   2 = "Up"
+//   8 = "Down"
+//   [roms.TANK.gamepad]
+//   5 = "b"
+// Host keys are SDL key names ("A", "Space", "Left", "Keypad 8", ...) and buttons are SDL game
+// controller button names (a, b, x, y, back, guide, start, leftstick, rightstick, leftshoulder,
+// rightshoulder, dpup, dpdown, dpleft, dpright). Only the part of TOML a keymap needs is
+// understood: tables, strings and arrays of strings.
+use std::error::Error;
+use std::fmt;
+
+pub const PRESETS: [&str; 4] = ["qwerty", "azerty", "dvorak", "numpad"];
+
+// Host keys for CHIP-8 keys 0-F, by where they sit on the COSMAC VIP keypad:
+//   1 2 3 C
+//   4 5 6 D
+//   7 8 9 E
+//   A 0 B F
+// is played on the 4x4 block under the 1-4 keys
+const QWERTY: [&str; 16] = ["X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V"];
+const AZERTY: [&str; 16] = ["X", "1", "2", "3", "A", "Z", "E", "Q", "S", "D", "W", "C", "4", "R", "F", "V"];
+// P lands on D here, the frontend's pause hotkey is also on the Pause key for this
+const DVORAK: [&str; 16] = ["Q", "1", "2", "3", "'", ",", ".", "A", "O", "E", ";", "J", "4", "P", "U", "K"];
+// the digits on their own keys, which puts 2/4/6/8 on the numpad arrows
+const NUMPAD: [&str; 16] = ["Keypad 0", "Keypad 1", "Keypad 2", "Keypad 3", "Keypad 4", "Keypad 5", "Keypad 6", "Keypad 7",
+    "Keypad 8", "Keypad 9", "Keypad /", "Keypad *", "Keypad -", "Keypad +", "Keypad Enter", "Keypad ."];
+
+// Gamepad buttons for CHIP-8 keys 0-F, the D-pad is 2/4/6/8 which most games use as directions
+const GAMEPAD: [&[&str]; 16] = [&["b"], &[], &["dpup"], &[], &["dpleft"], &["a"], &["dpright"], &["x"],
+    &["dpdown"], &["y"], &["back"], &["start"], &["leftshoulder"], &["rightshoulder"], &[], &[]];
+
+// Where in the keymap file parsing failed, lines start at 1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeymapError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for KeymapError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keymap {
+    // host key names for each CHIP-8 key
+    keys: [Vec<String>; 16],
+    // gamepad button names for each CHIP-8 key
+    buttons: [Vec<String>; 16],
+}
+
+impl Keymap {
+    pub fn preset(name: &str) -> Option<Self> {
+        let keys = match name.to_ascii_lowercase().as_str() {
+            "qwerty" => QWERTY,
+            "azerty" => AZERTY,
+            "dvorak" => DVORAK,
+            "numpad" => NUMPAD,
+            _ => return None,
+        };
+
+        Some(Self {
+            keys: keys.map(|key| vec![key.to_string()]),
+            buttons: GAMEPAD.map(|buttons| buttons.iter().map(|button| button.to_string()).collect()),
+        })
+    }
+
+    // Parse a keymap file, rom_name picks the [roms.<name>] table to apply (the ROM's file name,
+    // with or without its extension)
+    pub fn parse(source: &str, rom_name: Option<&str>) -> Result<Self, KeymapError> {
+        let mut keymap = Keymap::default();
+        let mut rom_entries = Vec::new();
+
+        for entry in parse_toml(source)? {
+            match entry.table.as_slice() {
+                [] => keymap.apply(&entry, false)?,
+                [gamepad] if gamepad == "gamepad" => keymap.apply(&entry, true)?,
+                [roms, name] if roms == "roms" => {
+                    if rom_name.is_some_and(|rom_name| matches_rom(name, rom_name)) {
+                        rom_entries.push((entry, false));
+                    }
+                },
+                [roms, name, gamepad] if roms == "roms" && gamepad == "gamepad" => {
+                    if rom_name.is_some_and(|rom_name| matches_rom(name, rom_name)) {
+                        rom_entries.push((entry, true));
+                    }
+                },
+                _ => return Err(entry.error(format!("unknown table [{}]", entry.table.join(".")))),
+            }
+        }
+
+        // the ROM's table goes on top of the main one wherever it is in the file
+        for (entry, gamepad) in &rom_entries {
+            keymap.apply(entry, *gamepad)?;
+        }
+        Ok(keymap)
+    }
+
+    // Bind the host keys to a CHIP-8 key, replacing what it had. A host key only ever presses
+    // one CHIP-8 key, so they're taken away from any other key they were bound to.
+    pub fn bind_keys(&mut self, key: usize, host_keys: Vec<String>) {
+        bind(&mut self.keys, key, host_keys);
+    }
+
+    // The same for gamepad buttons
+    pub fn bind_buttons(&mut self, key: usize, buttons: Vec<String>) {
+        bind(&mut self.buttons, key, buttons);
+    }
+
+    pub fn host_keys(&self, key: usize) -> &[String] {
+        &self.keys[key]
+    }
+
+    pub fn buttons(&self, key: usize) -> &[String] {
+        &self.buttons[key]
+    }
+
+    // The CHIP-8 key a host key presses, if it's bound
+    pub fn key_for(&self, host_key: &str) -> Option<usize> {
+        self.iter_keys().find(|(_, name)| name.eq_ignore_ascii_case(host_key)).map(|(key, _)| key)
+    }
+
+    // Every (CHIP-8 key, host key name) pair
+    pub fn iter_keys(&self) -> impl Iterator<Item = (usize, &str)> {
+        pairs(&self.keys)
+    }
+
+    // Every (CHIP-8 key, button name) pair
+    pub fn iter_buttons(&self) -> impl Iterator<Item = (usize, &str)> {
+        pairs(&self.buttons)
+    }
+
+    fn apply(&mut self, entry: &Entry, gamepad: bool) -> Result<(), KeymapError> {
+        if entry.key == "preset" && !gamepad {
+            let name = match &entry.value {
+                Value::String(name) => name,
+                Value::Array(_) => return Err(entry.error(String::from("preset should be a string"))),
+            };
+            // a preset is a keyboard layout, the gamepad bindings so far are kept
+            let preset = Keymap::preset(name).ok_or_else(|| entry.error(format!("unknown preset '{}', expected one of: {}", name, PRESETS.join(", "))))?;
+            self.keys = preset.keys;
+            return Ok(());
+        }
+
+        let key = parse_chip8_key(&entry.key).ok_or_else(|| {
+            let expected = if gamepad { "0-F" } else { "0-F or preset" };
+            entry.error(format!("'{}' is not a CHIP-8 key, expected {}", entry.key, expected))
+        })?;
+        let names = match &entry.value {
+            Value::String(name) => vec![name.clone()],
+            Value::Array(names) => names.clone(),
+        };
+        if gamepad {
+            self.bind_buttons(key, names);
+        }
+        else {
+            self.bind_keys(key, names);
+        }
+        Ok(())
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::preset("qwerty").unwrap()
+    }
+}
+
+fn bind(bindings: &mut [Vec<String>; 16], key: usize, names: Vec<String>) {
+    for bound in bindings.iter_mut() {
+        bound.retain(|bound| !names.iter().any(|name| name.eq_ignore_ascii_case(bound)));
+    }
+    bindings[key] = names;
+}
+
+fn pairs(bindings: &[Vec<String>; 16]) -> impl Iterator<Item = (usize, &str)> {
+    bindings.iter().enumerate().flat_map(|(key, names)| names.iter().map(move |name| (key, name.as_str())))
+}
+
+fn parse_chip8_key(name: &str) -> Option<usize> {
+    let name = name.trim_start_matches("0x");
+    if name.len() != 1 {
+        return None;
+    }
+    usize::from_str_radix(name, 16).ok()
+}
+
+fn matches_rom(table_name: &str, rom_name: &str) -> bool {
+    let stem = rom_name.rsplit_once('.').map_or(rom_name, |(stem, _)| stem);
+    table_name.eq_ignore_ascii_case(rom_name) || table_name.eq_ignore_ascii_case(stem)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Value {
+    String(String),
+    Array(Vec<String>),
+}
+
+// One key = value line along with the [table] it's in
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    table: Vec<String>,
+    key: String,
+    value: Value,
+    line: usize,
+}
+
+impl Entry {
+    fn error(&self, message: String) -> KeymapError {
+        KeymapError { line: self.line, message }
+    }
+}
+
+// The TOML subset: comments, [table.headers], key = "string" and key = ["list", "of", "strings"],
+// each on one line. Keys and table names can be bare or quoted.
+fn parse_toml(source: &str) -> Result<Vec<Entry>, KeymapError> {
+    let mut entries = Vec::new();
+    let mut table = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let mut scanner = Scanner { rest: line, line: number + 1 };
+        scanner.skip_space();
+        if scanner.at_end() {
+            continue;
+        }
+
+        if scanner.eat('[') {
+            table = vec![scanner.key()?];
+            while scanner.eat('.') {
+                table.push(scanner.key()?);
+            }
+            scanner.expect(']')?;
+        }
+        else {
+            let key = scanner.key()?;
+            scanner.expect('=')?;
+            let value = scanner.value()?;
+            entries.push(Entry { table: table.clone(), key, value, line: number + 1 });
+        }
+
+        if !scanner.at_end() {
+            return Err(scanner.error(format!("unexpected '{}'", scanner.rest.trim())));
+        }
+    }
+
+    Ok(entries)
+}
+
+struct Scanner<'a> {
+    rest: &'a str,
+    line: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn error(&self, message: String) -> KeymapError {
+        KeymapError { line: self.line, message }
+    }
+
+    // a comment counts as the end of the line
+    fn skip_space(&mut self) {
+        self.rest = self.rest.trim_start();
+        if self.rest.starts_with('#') {
+            self.rest = "";
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.rest.is_empty()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                self.skip_space();
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), KeymapError> {
+        if self.eat(c) {
+            Ok(())
+        }
+        else {
+            Err(self.error(format!("expected '{}'", c)))
+        }
+    }
+
+    fn key(&mut self) -> Result<String, KeymapError> {
+        if self.rest.starts_with('"') {
+            return self.string();
+        }
+
+        let length = self.rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-')).unwrap_or(self.rest.len());
+        if length == 0 {
+            return Err(self.error(String::from("expected a key")));
+        }
+        let key = self.rest[..length].to_string();
+        self.rest = &self.rest[length..];
+        self.skip_space();
+        Ok(key)
+    }
+
+    fn value(&mut self) -> Result<Value, KeymapError> {
+        if self.eat('[') {
+            let mut items = Vec::new();
+            while !self.eat(']') {
+                items.push(self.string()?);
+                if !self.eat(',') {
+                    self.expect(']')?;
+                    break;
+                }
+            }
+            Ok(Value::Array(items))
+        }
+        else {
+            Ok(Value::String(self.string()?))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, KeymapError> {
+        let mut chars = match self.rest.strip_prefix('"') {
+            Some(rest) => rest.char_indices(),
+            None => return Err(self.error(String::from("expected a quoted string"))),
+        };
+
+        let mut string = String::new();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 2..];
+                    self.skip_space();
+                    return Ok(string);
+                },
+                '\\' => match chars.next() {
+                    Some((_, '"')) => string.push('"'),
+                    Some((_, '\\')) => string.push('\\'),
+                    _ => return Err(self.error(String::from("only \\\" and \\\\ escapes are supported"))),
+                },
+                _ => string.push(c),
+            }
+        }
+
+        Err(self.error(String::from("unterminated string")))
+    }
+}
@@ -0,0 +1,11 @@
+// Key presses and releases in the order they happened, the frontend hands them to
+// Processor::key_event so a key that goes down and up between two frames still counts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    // CHIP-8 key 0-F
+    pub key: u8,
+    // true for a press, false for a release
+    pub down: bool,
+    // milliseconds, only the order matters
+    pub timestamp: u32,
+}
@@ -2,19 +2,39 @@
 
 pub const CHIP8_SCREEN_WIDTH: usize = 64;
 pub const CHIP8_SCREEN_HEIGHT: usize = 32;
+pub const SCHIP_SCREEN_WIDTH: usize = 128;
+pub const SCHIP_SCREEN_HEIGHT: usize = 64;
 pub const CHIP8_RAM_SIZE_BYTES: usize = 4096;
 pub const CHIP8_START_OF_PROGRAM: usize = 512;
 pub const CHIP8_PROGRAM_SIZE: usize = 3584;
+pub const XOCHIP_RAM_SIZE_BYTES: usize = 65536;
+pub const XOCHIP_PROGRAM_SIZE: usize = 65024;
 
+pub mod assembler;
+pub mod clock;
+pub mod debugger;
+pub mod disassembler;
 pub mod drivers;
+pub mod error;
+pub mod framebuffer;
+pub mod gdbstub;
+pub mod instruction;
+pub mod keymap;
+pub mod keypad;
+pub mod memory;
+pub mod png;
 pub mod processor;
+pub mod quirks;
+pub mod random;
+pub mod rewind;
+pub mod savestate;
+pub mod scheduler;
+pub mod snapshot;
+pub mod trace;
+pub mod watchpoint;
 
-use drivers::CartridgeDriver;
-use drivers::InputDriver;
-use drivers::DisplayDriver;
-
-
-pub type Program = [u8; crate::CHIP8_PROGRAM_SIZE];
+// Big enough for an XO-CHIP program, CHIP-8 programs only use the first CHIP8_PROGRAM_SIZE bytes
+pub type Program = [u8; crate::XOCHIP_PROGRAM_SIZE];
 
 pub const FONT_SET: [u8; 80] = [
     0xF0,
@@ -98,3 +118,168 @@ pub const FONT_SET: [u8; 80] = [
     0x80,
     0x80,
 ];
+
+// SUPER-CHIP 8x10 digits used by Fx30, stored in ram right after FONT_SET
+pub const BIG_FONT_START: usize = 80;
+pub const BIG_FONT_SET: [u8; 160] = [
+    0x3C,
+    0x7E,
+    0xE7,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xE7,
+    0x7E,
+    0x3C,
+    0x18,
+    0x38,
+    0x58,
+    0x18,
+    0x18,
+    0x18,
+    0x18,
+    0x18,
+    0x18,
+    0x3C,
+    0x3E,
+    0x7F,
+    0xC3,
+    0x06,
+    0x0C,
+    0x18,
+    0x30,
+    0x60,
+    0xFF,
+    0xFF,
+    0x3C,
+    0x7E,
+    0xC3,
+    0x03,
+    0x0E,
+    0x0E,
+    0x03,
+    0xC3,
+    0x7E,
+    0x3C,
+    0x06,
+    0x0E,
+    0x1E,
+    0x36,
+    0x66,
+    0xC6,
+    0xFF,
+    0xFF,
+    0x06,
+    0x06,
+    0xFF,
+    0xFF,
+    0xC0,
+    0xC0,
+    0xFC,
+    0xFE,
+    0x03,
+    0xC3,
+    0x7E,
+    0x3C,
+    0x3E,
+    0x7C,
+    0xE0,
+    0xC0,
+    0xFC,
+    0xFE,
+    0xC3,
+    0xC3,
+    0x7E,
+    0x3C,
+    0xFF,
+    0xFF,
+    0x03,
+    0x06,
+    0x0C,
+    0x18,
+    0x30,
+    0x60,
+    0x60,
+    0x60,
+    0x3C,
+    0x7E,
+    0xC3,
+    0xC3,
+    0x7E,
+    0x7E,
+    0xC3,
+    0xC3,
+    0x7E,
+    0x3C,
+    0x3C,
+    0x7E,
+    0xC3,
+    0xC3,
+    0x7F,
+    0x3F,
+    0x03,
+    0x03,
+    0x3E,
+    0x7C,
+    0x7E,
+    0xFF,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xFF,
+    0xFF,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xFC,
+    0xFC,
+    0xC3,
+    0xC3,
+    0xFC,
+    0xFC,
+    0xC3,
+    0xC3,
+    0xFC,
+    0xFC,
+    0x3C,
+    0xFF,
+    0xC3,
+    0xC0,
+    0xC0,
+    0xC0,
+    0xC0,
+    0xC3,
+    0xFF,
+    0x3C,
+    0xFC,
+    0xFE,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xFE,
+    0xFC,
+    0xFF,
+    0xFF,
+    0xC0,
+    0xC0,
+    0xFF,
+    0xFF,
+    0xC0,
+    0xC0,
+    0xFF,
+    0xFF,
+    0xFF,
+    0xFF,
+    0xC0,
+    0xC0,
+    0xFF,
+    0xFF,
+    0xC0,
+    0xC0,
+    0xC0,
+    0xC0,
+];
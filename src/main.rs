@@ -1,36 +1,326 @@
 //instead of crate:: use chip8_emulator_rust:: only in main.rs
-use chip8_emulator_rust::{CHIP8_SCREEN_HEIGHT,CHIP8_SCREEN_WIDTH, CHIP8_START_OF_PROGRAM, drivers::CartridgeDriver, drivers::InputDriver, drivers::DisplayDriver, processor::Processor, Program};
-use std::{time, thread};
+use chip8_emulator_rust::{CHIP8_START_OF_PROGRAM, clock::TIMER_HZ, keypad::KeyEvent, drivers::CartridgeDriver, drivers::InputDriver, drivers::{DisplayDriver, DEFAULT_SCALE}, drivers::{AudioDriver, Waveform, DEFAULT_FREQUENCY, DEFAULT_VOLUME}, debugger::Debugger, error::ErrorPolicy, gdbstub::{GdbStub, Session}, keymap::Keymap, processor::Processor, quirks::Platform, rewind::{self, Rewind}, savestate, scheduler::{Scheduler, FAST_FORWARD, FAST_FORWARD_MORE, SLOW_MOTION}, trace::{self, Tracer}};
+use sdl2::keyboard::{Keycode, LSHIFTMOD, RSHIFTMOD};
+use std::{env, fs, io, path::Path, process, thread};
 
-struct video {
-   screen: [[bool; CHIP8_SCREEN_WIDTH]; CHIP8_SCREEN_HEIGHT],
-}
+const USAGE: &str = "\
+usage: chip8-emulator-rust <rom> [options]
+  --scale <n>             window size of a hires pixel, 1 to 20 (default 5)
+  --ips <n>               instructions per second, 1 to 100000 (default 700)
+  --ipf <n>               instructions per 60Hz frame instead, 1 to 1666
+  --quirks <profile>      vip, chip48, schip, xochip or modern (default modern)
+  --keymap <preset|file>  qwerty, azerty, dvorak, numpad or a TOML keymap file (default qwerty)
+                          the file can also bind gamepad buttons, per ROM too
+  --debug                 run under the command line debugger, F12 breaks into it
+  --trace <file>          write every executed instruction to file
+  --trace-range <a>-<b>   only trace instructions at hex addresses a to b, can be repeated
+  --trace-max <n>         stop tracing after n lines
+  --paused                start paused, P resumes (or the debugger prompt with --debug)
+  --fullscreen            fill the desktop instead of opening a window
+  --no-vsync              don't wait for the display to refresh, sleep between frames instead
+  --waveform <name>       sound timer tone: square, sine or triangle (default square)
+  --frequency <hz>        pitch of the tone, 20 to 20000 (default 440)
+  --volume <percent>      0 to 100 (default 25)
+  --mute                  start muted, M toggles the sound
+  --gdb <port>            wait for gdb to attach on port before running
+  --rewind <seconds>      how much play Backspace can rewind, 0 to 600 (default 60)
+while running: P or Pause pauses, N advances one frame while paused, hold Tab to fast-forward (shift+Tab
+for 4x), L toggles slow motion, hold Backspace to rewind, F1-F10 load and shift+F1-F10 save states";
+
+const MAX_SCALE: u32 = 20;
+// hotkeys the keymap can take over, since a bound key only ever goes to the game
+const HOTKEYS: [(&str, &str); 7] = [
+   ("P", "pause, Pause still does"),
+   ("N", "advance a frame"),
+   ("L", "toggle slow motion"),
+   ("M", "mute"),
+   ("Tab", "fast-forward"),
+   ("Backspace", "rewind"),
+   ("F12", "break into the debugger"),
+];
+const MAX_INSTRUCTIONS_PER_SECOND: u32 = 100_000;
 
 fn main() {
-   let sdl_context = sdl2::init().unwrap();
+   let mut rom_path = None;
+   let mut scale = DEFAULT_SCALE;
+   let mut instructions_per_second = None;
+   let mut platform = Platform::Modern;
+   let mut keymap_source = None;
+   let mut debug = false;
+   let mut trace_path = None;
+   let mut trace_ranges = Vec::new();
+   let mut trace_max = None;
+   let mut paused = false;
+   let mut fullscreen = false;
+   let mut vsync = true;
+   let mut gdb_port = None;
+   let mut waveform = Waveform::Square;
+   let mut frequency = DEFAULT_FREQUENCY as u32;
+   let mut volume = (DEFAULT_VOLUME * 100.0) as u32;
+   let mut mute = false;
+   let mut rewind_seconds = rewind::DEFAULT_SECONDS;
+
+   let mut args = env::args().skip(1);
+   while let Some(arg) = args.next() {
+      let mut value = |name: &str| args.next().unwrap_or_else(|| fail(&format!("{} needs a value", name)));
+      match arg.as_str() {
+         "--scale" => scale = parse_number("--scale", &value("--scale"), 1, MAX_SCALE),
+         "--ips" => instructions_per_second = Some(parse_number("--ips", &value("--ips"), 1, MAX_INSTRUCTIONS_PER_SECOND)),
+         "--ipf" => {
+            let instructions_per_frame = parse_number("--ipf", &value("--ipf"), 1, MAX_INSTRUCTIONS_PER_SECOND / TIMER_HZ);
+            instructions_per_second = Some(instructions_per_frame * TIMER_HZ);
+         },
+         "--quirks" => platform = value("--quirks").parse().unwrap_or_else(|error: String| fail(&error)),
+         "--keymap" => keymap_source = Some(value("--keymap")),
+         "--debug" => debug = true,
+         "--trace" => trace_path = Some(value("--trace")),
+         "--trace-range" => trace_ranges.push(trace::parse_range(&value("--trace-range")).unwrap_or_else(|error| fail(&format!("--trace-range {}", error)))),
+         "--trace-max" => trace_max = Some(trace::parse_max_lines(&value("--trace-max")).unwrap_or_else(|error| fail(&format!("--trace-max {}", error)))),
+         "--paused" => paused = true,
+         "--fullscreen" => fullscreen = true,
+         "--no-vsync" => vsync = false,
+         "--waveform" => waveform = value("--waveform").parse().unwrap_or_else(|error: String| fail(&error)),
+         "--frequency" => frequency = parse_number("--frequency", &value("--frequency"), 20, 20_000),
+         "--volume" => volume = parse_number("--volume", &value("--volume"), 0, 100),
+         "--mute" => mute = true,
+         "--rewind" => rewind_seconds = parse_number("--rewind", &value("--rewind"), 0, 600),
+         "--gdb" => gdb_port = Some(parse_number("--gdb", &value("--gdb"), 1, u16::MAX as u32) as u16),
+         "-h" | "--help" => {
+            println!("{}", USAGE);
+            return;
+         },
+         _ if arg.starts_with('-') => fail(&format!("unknown option '{}'", arg)),
+         _ if rom_path.is_some() => fail(&format!("only one ROM can be given, got '{}' as well", arg)),
+         _ => rom_path = Some(arg),
+      }
+   }
+
+   let rom_path = rom_path.unwrap_or_else(|| fail("no ROM given"));
+   if trace_path.is_none() && (!trace_ranges.is_empty() || trace_max.is_some()) {
+      fail("--trace-range and --trace-max need --trace");
+   }
+   let cartridge_driver = CartridgeDriver::open(&rom_path).unwrap_or_else(|error| fail(&format!("can't read {}: {}", rom_path, error)));
+   if cartridge_driver.size == 0 {
+      fail(&format!("{} is empty", rom_path));
+   }
+   let rom_name = Path::new(&rom_path).file_name().map_or(rom_path.clone(), |name| name.to_string_lossy().into_owned());
+   let keymap = match keymap_source {
+      None => Keymap::default(),
+      Some(source) => Keymap::preset(&source).unwrap_or_else(|| {
+         let keymap_file = fs::read_to_string(&source).unwrap_or_else(|error| fail(&format!("'{}' is not a keymap preset and can't be read: {}", source, error)));
+         Keymap::parse(&keymap_file, Some(&rom_name)).unwrap_or_else(|error| fail(&format!("{}: {}", source, error)))
+      }),
+   };
+   for (hotkey, action) in HOTKEYS {
+      if let Some(key) = keymap.key_for(hotkey) {
+         eprintln!("{} is bound to CHIP-8 key {:X} so it won't {}", hotkey, key, action);
+      }
+   }
+   // open the trace file before the window so a bad path doesn't flash one up
+   let tracer = trace_path.map(|path| {
+      let mut tracer = Tracer::to_file(&path).unwrap_or_else(|error| fail(&format!("can't create {}: {}", path, error)));
+      for &(start, end) in &trace_ranges {
+         tracer.add_range(start, end);
+      }
+      tracer.set_max_lines(trace_max);
+      tracer
+   });
 
-   let cartridge_driver = CartridgeDriver::new("./roms/TANK");
+   let sdl_context = sdl2::init().unwrap();
    let mut input_driver = InputDriver::new(&sdl_context);
-   let mut display_driver = DisplayDriver::new(&sdl_context);
+   input_driver.set_keymap(&keymap).unwrap_or_else(|error| fail(&error));
+   let mut display_driver = DisplayDriver::new(&sdl_context, &rom_name, scale, fullscreen, vsync);
+   // without an audio device the emulator runs silent
+   let mut audio_driver = AudioDriver::new(&sdl_context).map_err(|error| eprintln!("no sound: {}", error)).ok();
+   if let Some(audio_driver) = audio_driver.as_mut() {
+      audio_driver.set_waveform(waveform);
+      audio_driver.set_frequency(frequency as f32);
+      audio_driver.set_volume(volume as f32 / 100.0);
+      audio_driver.set_muted(mute);
+   }
 
 
    let program_size = cartridge_driver.size;
    let program = cartridge_driver.rom;
-   let mut processor = Processor::new();
-   processor.set_debug(1);
+   let mut processor = Processor::new(platform.quirks());
+   if let Some(instructions_per_second) = instructions_per_second {
+      processor.set_instructions_per_second(instructions_per_second);
+   }
    processor.load(program, program_size, CHIP8_START_OF_PROGRAM);
-   processor.print_file(program_size);
-   // pri
+   processor.set_tracer(tracer);
+   // a fault drops into the debugger prompt or stops in gdb instead of being logged and skipped
+   if debug || gdb_port.is_some() {
+      processor.set_error_policy(ErrorPolicy::Halt);
+   }
+   let rom_hash = savestate::rom_hash(&program[..program_size]);
 
-   loop{
+   // waits for gdb to attach before running the ROM
+   let mut gdb = gdb_port.map(|port| {
+      println!("waiting for gdb on port {}", port);
+      GdbStub::listen(port).unwrap_or_else(|error| fail(&format!("can't listen on port {}: {}", port, error)))
+   });
+
+   // with --paused the debugger prompts before the first instruction
+   let mut debugger = debug.then(Debugger::new);
+   if let Some(debugger) = debugger.as_mut() {
+      processor.print_file(program_size);
+      if paused {
+         debugger.pause();
+      }
+   }
+   let stdin = io::stdin();
+   let stdout = io::stdout();
+
+   let mut rewind = Rewind::new(rewind_seconds, rewind::DEFAULT_MAX_BYTES, rewind::DEFAULT_INTERVAL);
+   let mut scheduler = Scheduler::new();
+   if paused && debugger.is_none() {
+      scheduler.pause();
+   }
+   let mut slow_motion = false;
+   // presses and releases that haven't been through a frame yet, the loop can go round more
+   // than once per frame on a fast display
+   let mut key_events: Vec<KeyEvent> = Vec::new();
+
+   'frames: loop {
       let keypad = input_driver.poll().expect("Error retrieving input");
-      let vram = processor.cycle(keypad);
 
-      display_driver.draw(&vram);
+      // F1-F10 load a save state slot, shift+F1-F10 save to it
+      for &(keycode, keymod) in input_driver.key_presses() {
+         let slot = match save_slot(keycode) {
+            Some(slot) => slot,
+            None => continue,
+         };
+         let path = format!("{}.state{}", rom_path, slot);
+
+         if keymod.intersects(LSHIFTMOD | RSHIFTMOD) {
+            match savestate::save(&path, &processor.snapshot(), rom_hash) {
+               Ok(()) => println!("saved slot {}", slot),
+               Err(error) => eprintln!("can't save slot {}: {}", slot, error),
+            }
+         }
+         else {
+            match savestate::load(&path, rom_hash) {
+               Ok(snapshot) => {
+                  processor.restore(&snapshot);
+                  rewind.clear();
+                  println!("loaded slot {}", slot);
+               },
+               Err(error) => eprintln!("can't load slot {}: {}", slot, error),
+            }
+         }
+      }
+
+      // P or Pause pauses and resumes, N runs one frame while paused, L toggles slow motion, M mutes,
+      // F12 breaks into the debugger
+      for &(keycode, _) in input_driver.key_presses() {
+         match keycode {
+            Keycode::P | Keycode::Pause if debugger.is_none() => scheduler.toggle_pause(),
+            Keycode::N if debugger.is_none() => scheduler.advance_frame(),
+            Keycode::L => slow_motion = !slow_motion,
+            Keycode::M => if let Some(audio_driver) = audio_driver.as_mut() {
+               audio_driver.toggle_mute();
+            },
+            Keycode::F12 => if let Some(debugger) = debugger.as_mut() {
+               debugger.pause();
+            },
+            _ => {},
+         }
+      }
+
+      // hold tab to fast-forward, with shift for even faster
+      let speed = if input_driver.is_held(Keycode::Tab) {
+         if input_driver.is_held(Keycode::LShift) || input_driver.is_held(Keycode::RShift) { FAST_FORWARD_MORE } else { FAST_FORWARD }
+      }
+      else if slow_motion {
+         SLOW_MOTION
+      }
+      else {
+         1.0
+      };
+      scheduler.set_speed(speed);
+
+      // hold backspace to play the game backwards
+      let rewinding = input_driver.is_held(Keycode::Backspace);
+      if rewinding {
+         key_events.clear();
+      }
+      else {
+         key_events.extend_from_slice(input_driver.key_events());
+      }
+
+      for _ in 0..scheduler.update() {
+         if rewinding {
+            rewind.step_back(&mut processor);
+            continue;
+         }
+
+         // every press and release since the last frame, so a quick tap still finishes Fx0A
+         for event in key_events.drain(..) {
+            processor.key_event(event);
+         }
+
+         if let Some(stub) = gdb.as_mut() {
+            match stub.run_frame(&mut processor, keypad) {
+               Ok(Session::Attached) => {},
+               Ok(Session::Detached) => gdb = None,
+               Ok(Session::Ended) => break 'frames,
+               Err(error) => {
+                  eprintln!("gdb connection lost: {}", error);
+                  gdb = None;
+               },
+            }
+            // without gdb or the debugger the ROM carries on past faults again
+            if gdb.is_none() && debugger.is_none() {
+               processor.set_error_policy(ErrorPolicy::default());
+            }
+         }
+         else if let Some(debugger) = debugger.as_mut() {
+            if !debugger.run_frame(&mut processor, keypad, &mut stdin.lock(), &mut stdout.lock()) {
+               break 'frames;
+            }
+         }
+         else if let Err(error) = processor.run_frame(keypad) {
+            eprintln!("{}", error);
+            break 'frames;
+         }
 
-      // thread::sleep(time::Duration::from_millis(2));
+         rewind.record(&processor);
+
+         if processor.exited() {
+            break 'frames;
+         }
+      }
+
+      // the timer doesn't count down while paused, so don't leave the tone droning
+      if let Some(audio_driver) = audio_driver.as_mut() {
+         audio_driver.update(if scheduler.is_paused() { 0 } else { processor.sound_timer() });
+      }
+      // once per loop, which waits for the display to refresh with vsync
+      display_driver.draw(processor.framebuffer());
+
+      if !vsync {
+         thread::sleep(scheduler.time_to_next_frame());
+      }
    }
 
 }
 
+fn save_slot(keycode: Keycode) -> Option<usize> {
+   let slots = [Keycode::F1, Keycode::F2, Keycode::F3, Keycode::F4, Keycode::F5,
+      Keycode::F6, Keycode::F7, Keycode::F8, Keycode::F9, Keycode::F10];
+   slots.iter().position(|&slot| slot == keycode).map(|index| index + 1)
+}
+
+fn parse_number(name: &str, value: &str, min: u32, max: u32) -> u32 {
+   match value.parse() {
+      Ok(number) if (min..=max).contains(&number) => number,
+      Ok(_) => fail(&format!("{} must be between {} and {}, got {}", name, min, max, value)),
+      Err(_) => fail(&format!("{} expects a number, got '{}'", name, value)),
+   }
+}
 
+fn fail(message: &str) -> ! {
+   eprintln!("chip8-emulator-rust: {}", message);
+   eprintln!("{}", USAGE);
+   process::exit(2);
+}
@@ -0,0 +1,138 @@
+// RAM with a selectable behaviour for addresses past the end of memory.
+// ROMs happily point I near 0xFFF and then read or write a few bytes past it.
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MemoryPolicy {
+    // addresses wrap around to the start of RAM
+    #[default]
+    Wrap,
+    // out of bounds accesses raise an error
+    Fault,
+    // out of bounds accesses hit the last byte of RAM
+    Clamp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+// A single read or write made by an instruction, with the address it resolved to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub address: usize,
+    pub kind: AccessKind,
+}
+
+// Raised under MemoryPolicy::Fault, carries the address that was asked for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutOfBounds {
+    pub address: usize,
+}
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "address {:X} is out of bounds", self.address)
+    }
+}
+
+pub struct Memory {
+    bytes: Vec<u8>,
+    policy: MemoryPolicy,
+    // accesses are only recorded while tracking is on, so normal execution doesn't pay for it
+    tracking: bool,
+    accesses: Vec<Access>,
+}
+
+impl Memory {
+    pub fn new(size: usize) -> Self {
+        Self {
+            bytes: vec![0u8; size],
+            policy: MemoryPolicy::default(),
+            tracking: false,
+            accesses: Vec::new(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.bytes.len()
+    }
+
+    // Grow or shrink RAM, new bytes are zeroed
+    pub fn resize(&mut self, size: usize) {
+        self.bytes.resize(size, 0);
+    }
+
+    pub fn policy(&self) -> MemoryPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: MemoryPolicy) {
+        self.policy = policy;
+    }
+
+    // Turn the address an instruction asked for into an index into RAM
+    pub fn resolve(&self, address: usize) -> Result<usize, OutOfBounds> {
+        if address < self.bytes.len() {
+            return Ok(address);
+        }
+
+        match self.policy {
+            MemoryPolicy::Wrap => Ok(address % self.bytes.len()),
+            MemoryPolicy::Fault => Err(OutOfBounds { address }),
+            MemoryPolicy::Clamp => Ok(self.bytes.len() - 1),
+        }
+    }
+
+    // Read without recording the access (instruction fetches, debuggers)
+    pub fn peek(&self, address: usize) -> Result<u8, OutOfBounds> {
+        Ok(self.bytes[self.resolve(address)?])
+    }
+
+    pub fn read(&mut self, address: usize) -> Result<u8, OutOfBounds> {
+        let address = self.resolve(address)?;
+        self.record(address, AccessKind::Read);
+        Ok(self.bytes[address])
+    }
+
+    pub fn write(&mut self, address: usize, value: u8) -> Result<(), OutOfBounds> {
+        let address = self.resolve(address)?;
+        self.record(address, AccessKind::Write);
+        self.bytes[address] = value;
+        Ok(())
+    }
+
+    // Big endian 16 bit word, the way opcodes are stored
+    pub fn peek_word(&self, address: usize) -> Result<u16, OutOfBounds> {
+        Ok((self.peek(address)? as u16) << 8 | self.peek(address + 1)? as u16)
+    }
+
+    // Direct access for loading ROMs and fonts, bypasses the policy and isn't recorded
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+
+    pub fn set_tracking(&mut self, tracking: bool) {
+        self.tracking = tracking;
+        if !tracking {
+            self.accesses.clear();
+        }
+    }
+
+    // Accesses recorded since the last call
+    pub fn take_accesses(&mut self) -> Vec<Access> {
+        std::mem::take(&mut self.accesses)
+    }
+
+    fn record(&mut self, address: usize, kind: AccessKind) {
+        if self.tracking {
+            self.accesses.push(Access { address, kind });
+        }
+    }
+}
@@ -1,543 +1,968 @@
-use std::{thread, time};
-use std::time::Instant;
-use rand::{Rng};
-use crate::{CHIP8_SCREEN_WIDTH, CHIP8_SCREEN_HEIGHT};
+use crate::clock::Clock;
+use crate::{CHIP8_SCREEN_WIDTH, CHIP8_SCREEN_HEIGHT, SCHIP_SCREEN_WIDTH, SCHIP_SCREEN_HEIGHT};
+use crate::framebuffer::FrameBuffer;
+use crate::quirks::{InstructionSet, LoadStoreIndex, Quirks};
+use crate::error::{ErrorPolicy, ExecError, ExecErrorKind};
+use crate::memory::{Memory, MemoryPolicy};
+use crate::instruction::Instruction;
+use crate::random::Random;
+use crate::trace::Tracer;
+use crate::snapshot::{PackedScreen, Snapshot};
+use crate::keypad::KeyEvent;
 
 pub struct Processor {
-    ram: [u8; crate::CHIP8_RAM_SIZE_BYTES],
+    memory: Memory,
     program_size: usize,
-    vram: [[bool; crate::CHIP8_SCREEN_WIDTH]; crate::CHIP8_SCREEN_HEIGHT],
+    framebuffer: FrameBuffer,
+    // XO-CHIP Fn01: bitmask of the planes that drawing, clearing and scrolling affect
+    planes: u8,
     // pc would have been u12 but to index easier in rust it needs to be usize
     pc: u16,
     reg_index: u16,
     opcode: u16,
     reg: [u8; 16],
-    stack: [u16; 16],
-    stack_ptr: u8,
+    // keys held down during the current cycle
+    keypad: [bool; 16],
+    // return addresses, its length is the stack pointer
+    stack: Vec<u16>,
     keypad_irq: bool,
     keypad_irq_dest: u8,
+    // bitmask of the keys pressed since Fx0A started waiting
+    keypad_irq_pressed: u16,
     delay_timer: u8,
     sound_timer: u8,
-    debug: usize,
-    breakpoint: bool,
-    last_time: Instant,
-    timer_decrement: f64,
+    clock: Clock,
+    quirks: Quirks,
+    error_policy: ErrorPolicy,
+    // set once an error stopped execution under ErrorPolicy::Halt
+    halted: Option<ExecError>,
+    // SUPER-CHIP 00FD: the program asked the interpreter to exit
+    exited: bool,
+    // SUPER-CHIP Fx75/Fx85 user flags (the RPL registers of the HP-48)
+    rpl: [u8; 16],
+    // XO-CHIP F002/Fx3A: 1-bit sample played while the sound timer runs, and its pitch
+    audio_pattern: [u8; 16],
+    pitch: u8,
+    random: Random,
+    // writes a line per executed instruction when set
+    tracer: Option<Tracer>,
 }
 
 impl Processor {
-    pub fn new() -> Self{
+    pub fn new(quirks: Quirks) -> Self{
         Self {
-            ram: [0u8; crate::CHIP8_RAM_SIZE_BYTES],
+            memory: Memory::new(ram_size(quirks.instruction_set)),
             program_size: 0,
-            vram: [[false; crate::CHIP8_SCREEN_WIDTH]; crate::CHIP8_SCREEN_HEIGHT],
+            framebuffer: FrameBuffer::new(CHIP8_SCREEN_WIDTH, CHIP8_SCREEN_HEIGHT),
+            planes: 0b01,
             pc: 0,
             reg_index: 0,
             opcode: 0,
             reg: [0u8; 16],
-            stack: [0u16; 16],
-            stack_ptr: 0,
+            keypad: [false; 16],
+            stack: Vec::with_capacity(16),
             keypad_irq: false,
             keypad_irq_dest: 0,
+            keypad_irq_pressed: 0,
             delay_timer: 0,
             sound_timer: 0,
-            debug: 0,
-            breakpoint: true,
-            last_time: Instant::now(),
-            timer_decrement: 0 as f64,
+            clock: Clock::default(),
+            quirks,
+            error_policy: ErrorPolicy::default(),
+            halted: None,
+            exited: false,
+            rpl: [0u8; 16],
+            audio_pattern: [0u8; 16],
+            pitch: 64,
+            random: Random::from_entropy(),
+            tracer: None,
         }
     }
 
     // Load data into ram
     // Need program data, length of data, program start
     pub fn load(&mut self, program: crate::Program, program_size: usize, program_start: usize){
-        self.ram[program_start..(program_start+program_size)].copy_from_slice(&program[0..program_size]);
+        let ram = self.memory.as_mut_slice();
+        // a ROM too big for this platform's memory is cut off
+        let program_size = program_size.min(ram.len() - program_start);
+        ram[program_start..(program_start+program_size)].copy_from_slice(&program[0..program_size]);
         self.program_size = program_size;
 
-        for i in 0..crate::FONT_SET.len() {
-            self.ram[i] = crate::FONT_SET[i];
-        }
+        ram[..crate::FONT_SET.len()].copy_from_slice(&crate::FONT_SET);
+        ram[crate::BIG_FONT_START..(crate::BIG_FONT_START+crate::BIG_FONT_SET.len())].copy_from_slice(&crate::BIG_FONT_SET);
 
         self.pc = program_start as u16;
     }
 
     // Get opcode at current program counter
-    pub fn read_opcode(&self, pc: u16) -> u16{
-        return (self.ram[pc as usize] as u16) << 8 | (self.ram[pc as usize + 1]) as u16;
+    pub fn read_opcode(&self, pc: u16) -> Result<u16, ExecErrorKind>{
+        Ok(self.memory.peek_word(pc as usize)?)
     }
 
-    pub fn set_debug(&mut self, debug: usize){
-        self.debug = debug;
+    // Decode the instruction at pc, reading F000 nnnn's address from the word after it
+    pub fn read_instruction(&self, pc: u16) -> Result<Instruction, ExecErrorKind>{
+        let opcode = self.read_opcode(pc)?;
+        // only read the next word when it's needed, anything else can sit in the last word of memory
+        let next = if opcode == Instruction::LoadIndexLong(0).opcode() { self.read_opcode(pc.wrapping_add(2))? } else { 0 };
+        Ok(Instruction::decode_for(opcode, next, self.quirks.instruction_set))
     }
 
-    pub fn cycle(&mut self, keypad: [bool; 16]) -> [[bool; crate::CHIP8_SCREEN_WIDTH]; crate::CHIP8_SCREEN_HEIGHT]{
+    // Seed Cxnn's random numbers, for runs that have to be repeatable
+    pub fn seed_random(&mut self, seed: u64){
+        self.random = Random::new(seed);
+    }
 
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>){
+        self.tracer = tracer;
+    }
 
-        let duration = self.last_time.elapsed().as_millis() as f64;
+    pub fn tracer_mut(&mut self) -> Option<&mut Tracer> {
+        self.tracer.as_mut()
+    }
 
-        //decrement timers by 60Hz spec
-        self.timer_decrement += ((duration/1000 as f64) * 60 as f64);
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
 
-        if self.timer_decrement > 1 as f64{
-            let delay_timer: i16 = self.delay_timer as i16 - self.timer_decrement.floor() as i16;
-            let sound_timer: i16 = self.sound_timer as i16 - self.timer_decrement.floor() as i16;
+    pub fn set_quirks(&mut self, quirks: Quirks){
+        self.quirks = quirks;
+        self.memory.resize(ram_size(quirks.instruction_set));
+    }
 
-            self.delay_timer = if delay_timer < 0 {0} else {delay_timer as u8};
-            self.sound_timer = if sound_timer < 0 {0} else {sound_timer as u8};
+    // Width and height of the screen in the current display mode
+    pub fn resolution(&self) -> (usize, usize) {
+        self.framebuffer.resolution()
+    }
+
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        &self.framebuffer
+    }
+
+    // Whether the program ended itself with 00FD
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    // Copy of the machine state, see Snapshot
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.as_slice().to_vec(),
+            screen: PackedScreen::pack(&self.framebuffer),
+            planes: self.planes,
+            pc: self.pc,
+            index: self.reg_index,
+            opcode: self.opcode,
+            registers: self.reg,
+            stack: self.stack.clone(),
+            waiting_for_key: self.keypad_irq,
+            key_register: self.keypad_irq_dest,
+            keys_pressed: self.keypad_irq_pressed,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            exited: self.exited,
+            rpl: self.rpl,
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            random: self.random.state(),
+            quirks: self.quirks,
         }
+    }
 
-        self.timer_decrement -= self.timer_decrement.floor();
+    // Put the machine back the way it was when the snapshot was taken, this also clears a halt
+    pub fn restore(&mut self, snapshot: &Snapshot){
+        self.memory.resize(snapshot.memory.len());
+        self.memory.as_mut_slice().copy_from_slice(&snapshot.memory);
+        self.framebuffer = snapshot.screen.unpack();
+        self.planes = snapshot.planes;
+        self.pc = snapshot.pc;
+        self.reg_index = snapshot.index;
+        self.opcode = snapshot.opcode;
+        self.reg = snapshot.registers;
+        self.stack = snapshot.stack.clone();
+        self.keypad_irq = snapshot.waiting_for_key;
+        self.keypad_irq_dest = snapshot.key_register;
+        self.keypad_irq_pressed = snapshot.keys_pressed;
+        self.delay_timer = snapshot.delay_timer;
+        self.sound_timer = snapshot.sound_timer;
+        self.exited = snapshot.exited;
+        self.rpl = snapshot.rpl;
+        self.audio_pattern = snapshot.audio_pattern;
+        self.pitch = snapshot.pitch;
+        self.random = Random::new(snapshot.random);
+        self.quirks = snapshot.quirks;
+        self.halted = None;
+    }
 
+    // Whether Fx0A is blocking until a key is pressed
+    pub fn waiting_for_key(&self) -> bool {
+        self.keypad_irq
+    }
 
+    pub fn rpl_flags(&self) -> [u8; 16] {
+        self.rpl
+    }
 
-        self.last_time = Instant::now();
+    pub fn set_rpl_flags(&mut self, rpl: [u8; 16]){
+        self.rpl = rpl;
+    }
 
-        // Keypad Interrupt
-        if self.keypad_irq == true {
-            for k in 0..keypad.len(){
-                if keypad[k] == true {
-                    self.reg[self.keypad_irq_dest as usize] = k as u8;
-                    self.keypad_irq = false;
-                }
-            }
-            return self.vram;
-        }
+    // XO-CHIP audio pattern and the playback rate in Hz it maps to (4000 * 2^((pitch - 64) / 48))
+    pub fn audio_pattern(&self) -> [u8; 16] {
+        self.audio_pattern
+    }
 
-        if self.debug == 3{
-            //break at every instruction
-            if self.breakpoint == true {
-                for k in 0..keypad.len() {
-                    if keypad[k] == true {
-                        self.breakpoint = false;
-                    }
-                }
-                return self.vram;
-            }
+    pub fn audio_sample_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
 
-        }
+    pub fn instructions_per_second(&self) -> u32 {
+        self.clock.instructions_per_second()
+    }
 
-        // Delay & Sound Timers (TODO Move this to its own thread)
-        if self.delay_timer > 0{
-            self.delay_timer -= 1;
-        }
+    pub fn set_instructions_per_second(&mut self, instructions_per_second: u32){
+        self.clock.set_instructions_per_second(instructions_per_second);
+    }
 
-        if self.sound_timer > 0{
-            self.sound_timer -= 1;
-        }
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u16){
+        self.pc = pc;
+    }
+
+    // Opcode of the last instruction executed
+    pub fn opcode(&self) -> u16 {
+        self.opcode
+    }
+
+    pub fn registers(&self) -> [u8; 16] {
+        self.reg
+    }
+
+    pub fn set_register(&mut self, x: usize, value: u8){
+        self.reg[x] = value;
+    }
+
+    pub fn index(&self) -> u16 {
+        self.reg_index
+    }
+
+    pub fn set_index(&mut self, index: u16){
+        self.reg_index = index;
+    }
+
+    // Return addresses, innermost call last
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    // Grow or shrink the stack to the given depth, new entries are 0
+    pub fn set_stack_pointer(&mut self, depth: usize){
+        self.stack.resize(depth, 0);
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
 
-        if self.debug > 0{
-            clearscreen::clear().expect("failed to clear screen");
+    pub fn set_delay_timer(&mut self, value: u8){
+        self.delay_timer = value;
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8){
+        self.sound_timer = value;
+    }
+
+    // Count the delay and sound timers down once, should be called at 60Hz
+    pub fn tick_timers(&mut self){
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    // How many cycles the next 60Hz frame gets, for callers that step through a frame themselves
+    pub fn instructions_for_next_frame(&mut self) -> u32 {
+        self.clock.instructions_for_next_frame()
+    }
+
+    // Run one 60Hz frame: as many instructions as the clock rate allows, then a timer tick
+    pub fn run_frame(&mut self, keypad: [bool; 16]) -> Result<(), ExecError>{
+        for _ in 0..self.instructions_for_next_frame() {
+            self.cycle(keypad)?;
         }
+        self.tick_timers();
 
+        Ok(())
+    }
 
-        if self.debug == 1 {
-            println!("OP: {:04x}", self.opcode);
+    pub fn set_memory_policy(&mut self, policy: MemoryPolicy){
+        self.memory.set_policy(policy);
+    }
 
-            //print registers
-            for r in 0..self.reg.len(){
-                println!("V{:X} = {:2X}", r, self.reg[r]);
-            }
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    pub fn set_error_policy(&mut self, error_policy: ErrorPolicy){
+        self.error_policy = error_policy;
+    }
+
+    // The error that halted the processor, if any
+    pub fn halted(&self) -> Option<ExecError> {
+        self.halted
+    }
+
+    // Clear a halt so execution continues at the faulting instruction
+    pub fn resume(&mut self) {
+        self.halted = None;
+    }
 
-            println!("I = {:2X}", self.reg_index);
+    // A key going down or up, call it for every event before running the frame they happened in.
+    // cycle works out the changes from the keypad it's given for callers that only have that.
+    pub fn key_event(&mut self, event: KeyEvent) {
+        let key = (event.key & 0x0F) as usize;
+        self.keypad[key] = event.down;
 
-            println!("PC = {:2X}", self.pc);
+        if !self.keypad_irq {
+            return;
+        }
+        if event.down {
+            self.keypad_irq_pressed |= 1 << key;
+        }
+        else if self.quirks.key_wait_on_release && self.keypad_irq_pressed & (1 << key) != 0 {
+            self.reg[self.keypad_irq_dest as usize] = key as u8;
+            self.keypad_irq = false;
+        }
+    }
 
-            println!("\nDT = {}", self.delay_timer);
-            println!("ST = {}", self.sound_timer);
+    // Execute a single instruction, the timers are left to tick_timers
+    pub fn cycle(&mut self, keypad: [bool; 16]) -> Result<(), ExecError>{
+        if let Some(error) = self.halted {
+            return Err(error);
+        }
 
-            println!("\nSP = {:2X}", self.stack_ptr);
+        if self.exited {
+            return Ok(());
+        }
 
-            for s in 0..self.stack.len(){
-                println!("S{:X} = {:2X}", s, self.stack[s]);
+        // a key that changed since the last cycle without a key_event
+        for (key, &down) in keypad.iter().enumerate() {
+            if down != self.keypad[key] {
+                self.key_event(KeyEvent { key: key as u8, down, timestamp: 0 });
             }
-            //keep everything on the screen for a bit
-            thread::sleep(time::Duration::from_millis(150));
+        }
 
+        // Keypad Interrupt
+        if self.keypad_irq {
+            // the old behaviour, the last key held finishes the wait
+            if !self.quirks.key_wait_on_release {
+                for (k, &pressed) in keypad.iter().enumerate() {
+                    if pressed {
+                        self.reg[self.keypad_irq_dest as usize] = k as u8;
+                        self.keypad_irq = false;
+                    }
+                }
+            }
+            return Ok(());
         }
 
-        else if self.debug > 1 {
-            self.display();
+        let instruction = match self.read_instruction(self.pc) {
+            Ok(instruction) => {
+                self.opcode = instruction.opcode();
+                instruction
+            },
+            Err(kind) => return self.fault(kind),
+        };
+
+        if let Some(mut tracer) = self.tracer.take() {
+            tracer.record(self, instruction);
+            self.tracer = Some(tracer);
         }
 
-        let mut pc_advance = true;
-        self.opcode = self.read_opcode(self.pc);
+        self.execute(instruction)
+    }
 
-        // defining some variables to help with processing
-        // the naming is based on opcode definition
+    // Execute an instruction as if it was found at the current pc,
+    // moving the pc on and applying the error policy the same way cycle does
+    pub fn execute(&mut self, instruction: Instruction) -> Result<(), ExecError> {
+        self.opcode = instruction.opcode();
 
-        // just put all the nibbles in a tuple
-        let nibbles = (
-            ((self.opcode & 0xF000) >> 12) as u8,
-            ((self.opcode & 0x0F00) >> 8) as u8,
-            ((self.opcode & 0x00F0) >> 4) as u8,
-            ((self.opcode & (0x000F) as u16)) as u8
-            );
+        match self.execute_instruction(instruction) {
+            Ok(true) => {
+                self.pc = self.pc.wrapping_add(instruction.size());
+                Ok(())
+            },
+            Ok(false) => Ok(()),
+            Err(kind) => self.fault(kind),
+        }
+    }
 
-        // dbg!(nibbles);
+    // Handle a fault of the current instruction according to the error policy
+    fn fault(&mut self, kind: ExecErrorKind) -> Result<(), ExecError> {
+        let error = ExecError { pc: self.pc, opcode: self.opcode, kind };
+        match self.error_policy {
+            ErrorPolicy::Halt => {
+                // leave the pc on the faulting instruction
+                self.halted = Some(error);
+                return Err(error);
+            },
+            ErrorPolicy::Log => eprintln!("{}", error),
+            ErrorPolicy::Ignore => {},
+        }
 
-        //_*** First nibble of opcode
-        //*X**
-        //**Y*
-        //***N
-        let (nibble, x, y, n) = nibbles;
+        // skip over the faulting instruction
+        self.pc = self.pc.wrapping_add(2);
+        Ok(())
+    }
 
-        //*NNN
-        let nnn = self.opcode & 0x0FFF;
+    // returns whether the pc should move on to the next instruction
+    fn execute_instruction(&mut self, instruction: Instruction) -> Result<bool, ExecErrorKind> {
+        let mut pc_advance = true;
 
-        //**NN
-        let nn = (self.opcode & 0x00FF) as u8;
+        let superchip = self.quirks.instruction_set >= InstructionSet::SuperChip;
 
-        match nibbles {
+        match instruction {
 
             // http://devernay.free.fr/hacks/chip8/C8TECH10.HTM#0.0
             // 00E0: CLS
             // clear the screen
-            (0x0, 0x0, 0xE, 0x0) => {
-                self.vram = [[false; crate::CHIP8_SCREEN_WIDTH]; crate::CHIP8_SCREEN_HEIGHT];
+            Instruction::Clear => {
+                self.framebuffer.clear(self.planes);
             },
 
             // 00EE: RET
             // return from subroutine (return)
-            (0x0, 0x0, 0xE, 0xE) => {
-                if self.stack_ptr > 0 {
-                    self.pc = self.stack[self.stack_ptr as usize - 1];
-                    //unnessesary but good for debugging
-                    self.stack[self.stack_ptr as usize - 1] = 0;
-                    self.stack_ptr = self.stack_ptr - 1;
+            Instruction::Return => {
+                if let Some(address) = self.stack.pop() {
+                    self.pc = address;
                 }
                 else {
-                    println!("EXECUTE: return: {:04x} ERROR no address to return to", self.opcode);
+                    return Err(ExecErrorKind::StackUnderflow);
                 }
-                // pc_advance = false;
+            },
+
+            // 00Cn: SCD nibble (SUPER-CHIP)
+            // Scroll the display down n pixels
+            Instruction::ScrollDown(n) => {
+                self.framebuffer.scroll(0, n as isize, self.planes);
+            },
+
+            // 00Dn: SCU nibble (XO-CHIP)
+            // Scroll the display up n pixels
+            Instruction::ScrollUp(n) => {
+                self.framebuffer.scroll(0, -(n as isize), self.planes);
+            },
+
+            // 00FB: SCR (SUPER-CHIP)
+            // Scroll the display right 4 pixels
+            Instruction::ScrollRight => {
+                self.framebuffer.scroll(4, 0, self.planes);
+            },
+
+            // 00FC: SCL (SUPER-CHIP)
+            // Scroll the display left 4 pixels
+            Instruction::ScrollLeft => {
+                self.framebuffer.scroll(-4, 0, self.planes);
+            },
+
+            // 00FD: EXIT (SUPER-CHIP)
+            // Exit the interpreter
+            Instruction::Exit => {
+                self.exited = true;
+                pc_advance = false;
+            },
+
+            // 00FE: LOW (SUPER-CHIP)
+            // Switch to the 64x32 lores mode
+            Instruction::LowRes => {
+                self.framebuffer.set_resolution(CHIP8_SCREEN_WIDTH, CHIP8_SCREEN_HEIGHT);
+            },
+
+            // 00FF: HIGH (SUPER-CHIP)
+            // Switch to the 128x64 hires mode
+            Instruction::HighRes => {
+                self.framebuffer.set_resolution(SCHIP_SCREEN_WIDTH, SCHIP_SCREEN_HEIGHT);
             },
 
             // 0NNN: SYS addr
             // 'Call' calling machine code routine
-            (0x0, _, _, _) => {
-                println!("EXECUTE: SYS addr: {:04x}", self.opcode);
+            Instruction::Sys(_) => {
+                return Err(ExecErrorKind::SysCall);
             },
 
             // 1NNN: JP addr
             // Jump to address NNN
-            (0x1, _, _, _) => {
+            Instruction::Jump(nnn) => {
                 self.pc = nnn;
-                // println!("Setting program counter to: {}", nnn);
                 pc_advance = false;
             },
 
             // 2NNN: CALL addr
             // Call subroutine at NNN (goto NNN;)
-            (0x2, _, _, _) => {
-                self.stack_ptr = self.stack_ptr + 1;
-                self.stack[self.stack_ptr as usize - 1] = self.pc;
+            Instruction::Call(nnn) => {
+                if self.quirks.stack_depth.is_some_and(|depth| self.stack.len() >= depth) {
+                    return Err(ExecErrorKind::StackOverflow);
+                }
+                self.stack.push(self.pc);
                 self.pc = nnn;
                 pc_advance = false;
-
             },
 
             // 3xNN: SE Vx, byte
             // Skip next instruction if Vx == NN
-            (0x3, _, _, _) => {
+            Instruction::SkipIfEqual(x, nn) => {
                 if self.reg[x as usize] == nn {
-                    self.pc += 2;
+                    self.skip_next_instruction()?;
                 }
             },
 
             // 4xNN: SNE Vx, byte
             // Skip next instruction if Vx != NN
-            (0x4, _, _, _) => {
+            Instruction::SkipIfNotEqual(x, nn) => {
                 if self.reg[x as usize] != nn {
-                    self.pc += 2;
+                    self.skip_next_instruction()?;
                 }
             },
 
             // 5xy0: SE Vx, Vy
             // Skip next instruction if Vx == Vy
-            (0x5, _, _, 0x0) => {
+            Instruction::SkipIfRegistersEqual(x, y) => {
                 if self.reg[x as usize] == self.reg[y as usize] {
-                    self.pc += 2;
+                    self.skip_next_instruction()?;
+                }
+            },
+
+            // 5xy2: SAVE Vx - Vy (XO-CHIP)
+            // Store registers Vx through Vy (in either direction) in memory starting at location I, I is left alone
+            Instruction::SaveRange(x, y) => {
+                for (offset, r) in register_range(x, y).enumerate() {
+                    self.memory.write(self.reg_index as usize + offset, self.reg[r])?;
+                }
+            },
+
+            // 5xy3: LOAD Vx - Vy (XO-CHIP)
+            // Read registers Vx through Vy (in either direction) from memory starting at location I, I is left alone
+            Instruction::LoadRange(x, y) => {
+                for (offset, r) in register_range(x, y).enumerate() {
+                    self.reg[r] = self.memory.read(self.reg_index as usize + offset)?;
                 }
             },
 
             // 6xNN: LD Vx, byte
             // Sets Vx to NN
-            (0x6, _, _, _) => {
+            Instruction::Load(x, nn) => {
                 self.reg[x as usize] = nn;
             },
 
             // 7xNN: ADD Vx, byte
             // Add NN to Vx (don't set carry flag)
-            (0x7, _, _, _) => {
-                self.reg[x as usize] = (self.reg[x as usize] as u16 + nn as u16) as u8;
+            Instruction::Add(x, nn) => {
+                self.reg[x as usize] = self.reg[x as usize].wrapping_add(nn);
             },
 
             // 8xy0: LD Vx, Vy
             // Vx = Vy
-            (0x8, _, _, 0x0) => {
+            Instruction::Move(x, y) => {
                 self.reg[x as usize] = self.reg[y as usize];
             },
 
             // 8xy1: OR Vx, Vy
             // Vx = Vx | Vy (OR)
-            (0x8, _, _, 0x1) => {
-                self.reg[x as usize] = self.reg[x as usize] | self.reg[y as usize];
+            Instruction::Or(x, y) => {
+                self.reg[x as usize] |= self.reg[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.reg[0xF] = 0;
+                }
             },
 
             // 8xy2: AND Vx, Vy
             // Vx = Vx & Vy (AND)
-            (0x8, _, _, 0x2) => {
-                self.reg[x as usize] = self.reg[x as usize] & self.reg[y as usize];
+            Instruction::And(x, y) => {
+                self.reg[x as usize] &= self.reg[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.reg[0xF] = 0;
+                }
             },
 
             // 8xy3: XOR Vx, Vy
             // Vx = Vx ^ Vy (XOR)
-            (0x8, _, _, 0x3) => {
-                self.reg[x as usize] = self.reg[x as usize] ^ self.reg[y as usize];
+            Instruction::Xor(x, y) => {
+                self.reg[x as usize] ^= self.reg[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.reg[0xF] = 0;
+                }
             },
 
             // 8xy4: ADD Vx, Vy
             // Vx = Vx + Vy, Set VF (last register) if carry occurs (true false)
-            (0x8, _, _, 0x4) => {
+            Instruction::AddRegisters(x, y) => {
                 let result = self.reg[x as usize] as u16 + self.reg[y as usize] as u16;
-                let mut carry = false;
-                if (result > 255){
-                    carry = true;
-                }
-                self.reg[0xF] = carry as u8;
+                let carry = result > 255;
+                // VF is written last so it holds the flag even when it's the destination
                 self.reg[x as usize] = result as u8;
+                self.reg[0xF] = carry as u8;
             },
 
             // 8xy5: SUB Vx, Vy
             // Vx = Vx - Vy, Set VF (last register) if borrow does NOT occur (true false)
-            (0x8, _, _, 0x5) => {
+            Instruction::Sub(x, y) => {
                 let result = self.reg[x as usize].wrapping_sub(self.reg[y as usize]);
 
-                let mut not_borrow = self.reg[x as usize] >= self.reg[y as usize];
+                let not_borrow = self.reg[x as usize] >= self.reg[y as usize];
 
-                self.reg[0xF] = not_borrow as u8;
                 self.reg[x as usize] = result;
+                self.reg[0xF] = not_borrow as u8;
             },
 
             // 8xy6: SHR Vx {, Vy}
             // Vx = Vx >> 1, Set VF to LSB of Vx (0101 = 1011 >> 1, VF = 1)
-            (0x8, _, _, 0x6) => {
-                self.reg[0xF] = self.reg[x as usize] & 0x0001;
-                self.reg[x as usize] = self.reg[x as usize] >> 1;
+            // On the COSMAC VIP Vy is shifted and stored in Vx instead
+            Instruction::ShiftRight(x, y) => {
+                let value = if self.quirks.shift_uses_vy { self.reg[y as usize] } else { self.reg[x as usize] };
+                self.reg[x as usize] = value >> 1;
+                self.reg[0xF] = value & 0x01;
             },
 
             // 8xy7: SUBN Vx, Vy
             // Vx = Vy - Vx, set VF = NOT borrow.
-            (0x8, _, _, 0x7) => {
+            Instruction::SubReverse(x, y) => {
                 let result = self.reg[y as usize].wrapping_sub(self.reg[x as usize]);
 
-                let mut not_borrow = self.reg[y as usize] >= self.reg[x as usize];
+                let not_borrow = self.reg[y as usize] >= self.reg[x as usize];
 
-                self.reg[0xF] = not_borrow as u8;
                 self.reg[x as usize] = result;
+                self.reg[0xF] = not_borrow as u8;
             },
 
-            // 8xyE: SHR Vx {, Vy}
+            // 8xyE: SHL Vx {, Vy}
             // Vx = Vx << 1, Set VF to MSB of Vx (0101 = 1011 >> 1, VF = 1)
-            (0x8, _, _, 0xE) => {
-                self.reg[0xF] = self.reg[x as usize] >> 7;
-                self.reg[x as usize] = self.reg[x as usize] << 1;
+            // On the COSMAC VIP Vy is shifted and stored in Vx instead
+            Instruction::ShiftLeft(x, y) => {
+                let value = if self.quirks.shift_uses_vy { self.reg[y as usize] } else { self.reg[x as usize] };
+                self.reg[x as usize] = value << 1;
+                self.reg[0xF] = value >> 7;
             },
 
             // 9xy0 - SNE Vx, Vy
             // Skip next instruction if Vx != Vy
-            (0x9, _, _, 0x0) => {
+            Instruction::SkipIfRegistersNotEqual(x, y) => {
                 if self.reg[x as usize] != self.reg[y as usize] {
-                    self.pc += 2;
+                    self.skip_next_instruction()?;
                 }
             },
 
             // Annn - LD I, addr
             // Set I = nnn.
-            (0xA, _, _, _) => {
+            Instruction::LoadIndex(nnn) => {
                 self.reg_index = nnn;
             },
 
             // Bnnn - JP V0, addr
             // Jump to location nnn + V0.
-            (0xB, _, _, _) => {
-                self.pc = self.reg[0] as u16 + nnn;
+            // CHIP-48 and SUPER-CHIP read this as Bxnn: jump to xnn + Vx
+            Instruction::JumpOffset(nnn) => {
+                let x = (nnn >> 8) as usize;
+                let offset = if self.quirks.jump_uses_vx { self.reg[x] } else { self.reg[0] };
+                self.pc = offset as u16 + nnn;
                 pc_advance = false;
             },
 
             // Cxkk - RND Vx, byte
             // Set Vx = random byte AND kk.
             // The interpreter generates a random number from 0 to 255, which is then ANDed with the value kk. The results are stored in Vx
-            (0xC, _, _, _) => {
-                let random: u8 = rand::thread_rng().gen::<u8>();
+            Instruction::Random(x, nn) => {
+                let random = self.random.next_u8();
                 self.reg[x as usize] = random & nn;
             },
 
+            // Dxy0 - DRW Vx, Vy, 0 (SUPER-CHIP)
+            // Display a 16x16 sprite (two bytes per row) starting at memory location I at (Vx, Vy), set VF = collision.
+            Instruction::Draw(x, y, 0) if superchip => {
+                self.draw_sprite(self.reg[x as usize], self.reg[y as usize], 16, 16)?;
+            },
+
             // Dxyn - DRW Vx, Vy, nibble
             // Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
-            // Sprites shouldn't wrap they should clip! Only the position wraps https://www.reddit.com/r/EmuDev/comments/gft7r9/another_beginner_post_for_clarification_for_my/
-            (0xD, _, _, _) => {
-                let reg_x = self.reg[x as usize] as usize % CHIP8_SCREEN_WIDTH;
-                let reg_y = self.reg[y as usize] as usize % CHIP8_SCREEN_HEIGHT;
-
-                self.reg[0xF] = 0x0;
-
-                for row in 0..n as usize {
-                    let y_index = (reg_y + row);
-
-                    if y_index >= CHIP8_SCREEN_HEIGHT{
-                        break;
-                    }
-
-                    let mut vram_row = &mut self.vram[y_index];
-                    let sprite_row = self.ram[self.reg_index as usize + row];
-
-                    for col in 0..8 as usize {
-                        let x_index = (col + reg_x);
-
-                        if x_index >= CHIP8_SCREEN_WIDTH{
-                            break;
-                        }
-
-                        let previous_pixel = vram_row[x_index];
-
-                        vram_row[x_index] = vram_row[x_index] ^ ((sprite_row & (0x80 >> col)) != 0);
-
-                        // if the previous pixel was 1 and was replaced with a 0 there is collision
-                        if previous_pixel == true && vram_row[x_index] == false{
-                            self.reg[0xF]  = 0x1;
-                        }
-                    }
-                }
-
-                //need flag when display bit flip
+            Instruction::Draw(x, y, n) => {
+                self.draw_sprite(self.reg[x as usize], self.reg[y as usize], 8, n as usize)?;
             },
 
             // Ex9E - SKP Vx
             // Skip next instruction if key with the value of Vx is pressed.
-            (0xE, _, 0x9, 0xE) => {
-                if keypad[self.reg[x as usize] as usize] == true {
-                    self.pc += 2;
+            Instruction::SkipIfKey(x) => {
+                if self.keypad[(self.reg[x as usize] & 0x0F) as usize] {
+                    self.skip_next_instruction()?;
                 }
             },
 
             // ExA1 - SKNP Vx
             // Skip next instruction if key with the value of Vx is not pressed.
-            (0xE, _, 0xA, 0x1) => {
-                if keypad[self.reg[x as usize] as usize] == false {
-                    self.pc += 2;
+            Instruction::SkipIfNotKey(x) => {
+                if !self.keypad[(self.reg[x as usize] & 0x0F) as usize] {
+                    self.skip_next_instruction()?;
+                }
+            },
+
+            // F000 NNNN - LD I, long (XO-CHIP)
+            // Set I = the 16 bit address stored in the next word
+            Instruction::LoadIndexLong(nnnn) => {
+                self.reg_index = nnnn;
+            },
+
+            // Fn01 - PLANE n (XO-CHIP)
+            // Select the bitplanes that drawing, clearing and scrolling affect
+            Instruction::Plane(n) => {
+                self.planes = n & 0b11;
+            },
+
+            // F002 - AUDIO (XO-CHIP)
+            // Load the 16 byte audio pattern from memory starting at location I
+            Instruction::Audio => {
+                for i in 0..self.audio_pattern.len() {
+                    self.audio_pattern[i] = self.memory.read(self.reg_index as usize + i)?;
                 }
             },
 
             // Fx07 - LD Vx, DT
             // Set Vx = delay timer value.
-            (0xF, _, 0x0, 0x7) => {
+            Instruction::LoadDelay(x) => {
                 self.reg[x as usize] = self.delay_timer;
             },
 
             // Fx0A - LD Vx, K
             // Stop execution, wait for a key press, store the value of the key in Vx.
-            (0xF, _, 0x0, 0xA) => {
+            Instruction::WaitKey(x) => {
                 self.keypad_irq = true;
                 self.keypad_irq_dest = x;
+                self.keypad_irq_pressed = 0;
             },
 
             // Fx15 - LD DT, Vx
             // Set delay timer = Vx.
-            (0xF, _, 0x1, 0x5) => {
+            Instruction::SetDelay(x) => {
                 self.delay_timer = self.reg[x as usize];
             },
 
             // Fx18 - LD ST, Vx
             // Set sound timer = Vx.
-            (0xF, _, 0x1, 0x8) => {
+            Instruction::SetSound(x) => {
                 self.sound_timer = self.reg[x as usize];
             },
 
             // Fx1E - ADD I, Vx
             // Set I = I + Vx.
-            (0xF, _, 0x1, 0xE) => {
-                self.reg_index = (self.reg_index as u32 + self.reg[x as usize] as u32) as u16;
+            Instruction::AddIndex(x) => {
+                self.reg_index = self.reg_index.wrapping_add(self.reg[x as usize] as u16);
             },
 
             // Fx29 - LD F, Vx
             // Set I = location of sprite for digit Vx.
-            (0xF, _, 0x2, 0x9) => {
+            Instruction::LoadFont(x) => {
                 // fonts stored starting at ram[0] and each font takes 5 bytes of memory
-                self.reg_index = (self.reg[x as usize] * 5) as u16;
+                // only the low nibble selects a digit
+                self.reg_index = (self.reg[x as usize] & 0x0F) as u16 * 5;
+            },
 
+            // Fx30 - LD HF, Vx (SUPER-CHIP)
+            // Set I = location of the 8x10 sprite for digit Vx.
+            Instruction::LoadBigFont(x) => {
+                // big fonts are stored right after the small ones and each takes 10 bytes of memory
+                self.reg_index = (crate::BIG_FONT_START + (self.reg[x as usize] & 0x0F) as usize * 10) as u16;
             },
 
             // Fx33 - LD B, Vx
             // Store BCD representation of Vx in memory locations I, I+1, and I+2.
-            (0xF, _, 0x3, 0x3) => {
-                self.ram[self.reg_index as usize] = self.reg[x as usize] / 100;
-                self.ram[self.reg_index as usize + 1] = (self.reg[x as usize] % 100) / 10;
-                self.ram[self.reg_index as usize + 2] = self.reg[x as usize] % 10;
+            Instruction::StoreBcd(x) => {
+                self.memory.write(self.reg_index as usize, self.reg[x as usize] / 100)?;
+                self.memory.write(self.reg_index as usize + 1, (self.reg[x as usize] % 100) / 10)?;
+                self.memory.write(self.reg_index as usize + 2, self.reg[x as usize] % 10)?;
+            },
+
+            // Fx3A - PITCH Vx (XO-CHIP)
+            // Set the playback rate of the audio pattern
+            Instruction::Pitch(x) => {
+                self.pitch = self.reg[x as usize];
             },
 
             // Fx55 - LD [I], Vx
             // Store registers V0 through Vx in memory starting at location I.
-            (0xF, _, 0x5, 0x5) => {
+            Instruction::StoreRegisters(x) => {
                 let range = x as usize + 1;
                 for r in 0..range{
-                    self.ram[self.reg_index as usize + r] = self.reg[r];
+                    self.memory.write(self.reg_index as usize + r, self.reg[r])?;
                 }
+                self.advance_index_after_load_store(x);
             },
 
             // Fx65 - LD Vx, [I]
             // Read registers V0 through Vx from memory starting at location I.
-            (0xF, _, 0x6, 0x5) => {
+            Instruction::LoadRegisters(x) => {
                 let range = x as usize + 1;
                 for r in 0..range{
-                    self.reg[r] = self.ram[self.reg_index as usize + r];
+                    self.reg[r] = self.memory.read(self.reg_index as usize + r)?;
                 }
+                self.advance_index_after_load_store(x);
+            },
+
+            // Fx75 - LD R, Vx (SUPER-CHIP)
+            // Store registers V0 through Vx in the RPL user flags.
+            Instruction::StoreFlags(x) => {
+                let range = x as usize + 1;
+                self.rpl[..range].copy_from_slice(&self.reg[..range]);
             },
 
-            _ => {
-                println!("UNKNOWN INSTR: {:04x}", self.opcode)
+            // Fx85 - LD Vx, R (SUPER-CHIP)
+            // Read registers V0 through Vx from the RPL user flags.
+            Instruction::LoadFlags(x) => {
+                let range = x as usize + 1;
+                self.reg[..range].copy_from_slice(&self.rpl[..range]);
+            },
+
+            Instruction::Unknown(_) => {
+                return Err(ExecErrorKind::UnknownOpcode);
             }
         }
 
-        if(pc_advance){
-            self.pc += 2;
+        Ok(pc_advance)
+    }
+
+    // Skip the next instruction, which is 4 bytes long if it's the XO-CHIP F000 NNNN
+    fn skip_next_instruction(&mut self) -> Result<(), ExecErrorKind> {
+        let next = self.pc as usize + 2;
+        if self.quirks.instruction_set >= InstructionSet::XoChip && self.memory.peek_word(next)? == 0xF000 {
+            self.pc = self.pc.wrapping_add(4);
+        }
+        else {
+            self.pc = self.pc.wrapping_add(2);
+        }
+        Ok(())
+    }
+
+    // XOR a sprite of width 8 or 16 pixels onto the screen at (x, y), set VF = collision.
+    // Sprites shouldn't wrap they should clip! Only the position wraps https://www.reddit.com/r/EmuDev/comments/gft7r9/another_beginner_post_for_clarification_for_my/
+    // (unless the clip quirk is turned off, then the sprite wraps around to the other side)
+    // With several XO-CHIP planes selected the sprite data for each plane follows the previous one.
+    fn draw_sprite(&mut self, x: u8, y: u8, width: usize, height: usize) -> Result<(), ExecErrorKind> {
+        let (screen_width, screen_height) = self.resolution();
+        let reg_x = x as usize % screen_width;
+        let reg_y = y as usize % screen_height;
+        let bytes_per_row = width / 8;
+        let mut address = self.reg_index as usize;
+
+        self.reg[0xF] = 0x0;
+
+        for plane in [0b01u8, 0b10u8] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+
+            for row in 0..height {
+                // sprite rows are stored big endian, 16 pixel rows take two bytes
+                let mut sprite_row = 0u16;
+                for _ in 0..bytes_per_row {
+                    sprite_row = sprite_row << 8 | self.memory.read(address)? as u16;
+                    address += 1;
+                }
+
+                let mut y_index = reg_y + row;
+
+                if y_index >= screen_height{
+                    if self.quirks.clip_sprites {
+                        continue;
+                    }
+                    y_index %= screen_height;
+                }
+
+                for col in 0..width {
+                    let mut x_index = col + reg_x;
+
+                    if x_index >= screen_width{
+                        if self.quirks.clip_sprites {
+                            break;
+                        }
+                        x_index %= screen_width;
+                    }
+
+                    if sprite_row & (1 << (width - 1 - col)) == 0 {
+                        continue;
+                    }
+
+                    // if the previous pixel was 1 and was replaced with a 0 there is collision
+                    if self.framebuffer.toggle(x_index, y_index, plane) {
+                        self.reg[0xF]  = 0x1;
+                    }
+                }
+            }
         }
 
-        self.breakpoint = true;
-        return self.vram;
+        Ok(())
+    }
+
+    // Fx55/Fx65 either leave I alone or move it past the registers, depending on the platform
+    fn advance_index_after_load_store(&mut self, x: u8) {
+        match self.quirks.load_store {
+            LoadStoreIndex::Unchanged => {},
+            LoadStoreIndex::IncrementByX => {
+                self.reg_index = self.reg_index.wrapping_add(x as u16);
+            },
+            LoadStoreIndex::IncrementByXPlusOne => {
+                self.reg_index = self.reg_index.wrapping_add(x as u16 + 1);
+            },
+        }
     }
 
     // debug
     pub fn print_file(&self, program_size: usize) {
-        let mut pc = self.pc;
+        let ram = self.memory.as_slice();
+        // in usize and cut off at the end of RAM, a full XO-CHIP ROM reaches past 0xFFFF
+        let end = (self.pc as usize + program_size).min(ram.len());
 
-        while (pc < (program_size as u16 +self.pc)) {
-            let opcode = (self.ram[pc as usize] as u16) << 8 | (self.ram[pc as usize + 1]) as u16;
-            if(opcode != self.opcode) {
+        for pc in (self.pc as usize..end).step_by(2) {
+            let opcode = (ram[pc] as u16) << 8 | *ram.get(pc + 1).unwrap_or(&0) as u16;
+            if opcode != self.opcode {
                 print!("{:04x} ", opcode);
             }
             else{
                 print!("<{:04x}> ", opcode);
             }
-            if ((pc / 2 + 1) % 8 == 0) {
-                println!("");
+            if (pc / 2 + 1).is_multiple_of(8) {
+                println!();
             }
-            pc += 2;
         }
     }
 
     pub fn display(&self){
-        // println!("{:#?}", self.vram);
-        for row in 0..crate::CHIP8_SCREEN_HEIGHT{
-            for col in 0..crate::CHIP8_SCREEN_WIDTH{
-                if self.vram[row][col] == true{
-                    print!("*");
-                }
-                else{
-                    print!("_");
-                }
-            }
-            print!("\n");
+        print!("{}", self.framebuffer);
+    }
+}
 
-        }
+// XO-CHIP programs get the full 64K address space
+fn ram_size(instruction_set: InstructionSet) -> usize {
+    if instruction_set >= InstructionSet::XoChip {
+        crate::XOCHIP_RAM_SIZE_BYTES
+    }
+    else {
+        crate::CHIP8_RAM_SIZE_BYTES
     }
 }
 
+// Registers x through y, counting down when x > y
+fn register_range(x: u8, y: u8) -> impl Iterator<Item = usize> {
+    let (x, y) = (x as usize, y as usize);
+    let ascending = x <= y;
+    (0..=x.abs_diff(y)).map(move |i| if ascending { x + i } else { x - i })
+}
+
+impl Default for Processor {
+    fn default() -> Self {
+        Self::new(Quirks::default())
+    }
+}
@@ -0,0 +1,188 @@
+// Opcodes whose behaviour differed between the interpreters CHIP-8 ran on over the years.
+// A ROM only runs correctly under the rules of the platform it was written for,
+// see https://github.com/Timendus/chip8-test-suite#quirks-test for an overview.
+use std::fmt;
+use std::str::FromStr;
+
+// What Fx55/Fx65 leave in I once the registers are stored/loaded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadStoreIndex {
+    // I is left untouched (SUPER-CHIP 1.1)
+    Unchanged,
+    // I = I + x (CHIP-48)
+    IncrementByX,
+    // I = I + x + 1 (COSMAC VIP)
+    IncrementByXPlusOne,
+}
+
+// Which extensions to the original instruction set are understood
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstructionSet {
+    Chip8,
+    // SUPER-CHIP 1.1: hires mode, scrolling, 16x16 sprites, big font and RPL flags
+    SuperChip,
+    // XO-CHIP on top of SUPER-CHIP: 64K of memory, long I, bitplanes, register ranges and audio patterns
+    XoChip,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    pub instruction_set: InstructionSet,
+    // 8xy6/8xyE: shift Vy and store the result in Vx instead of shifting Vx in place
+    pub shift_uses_vy: bool,
+    // Fx55/Fx65: what happens to I afterwards
+    pub load_store: LoadStoreIndex,
+    // 8xy1/8xy2/8xy3: VF is reset to 0 after the logic operation
+    pub logic_resets_vf: bool,
+    // Bnnn: jump to nnn + Vx (x being the top nibble of nnn) instead of nnn + V0
+    pub jump_uses_vx: bool,
+    // Dxyn: sprites are clipped at the edge of the screen instead of wrapping around
+    pub clip_sprites: bool,
+    // 2NNN: how many nested calls fit on the stack, None for no limit
+    pub stack_depth: Option<usize>,
+    // Fx0A: finish when a key pressed during the wait is released, like the COSMAC VIP, instead
+    // of as soon as any key is held
+    pub key_wait_on_release: bool,
+}
+
+impl Quirks {
+    // The original interpreter on the RCA COSMAC VIP
+    pub fn cosmac_vip() -> Self {
+        Self {
+            instruction_set: InstructionSet::Chip8,
+            shift_uses_vy: true,
+            load_store: LoadStoreIndex::IncrementByXPlusOne,
+            logic_resets_vf: true,
+            jump_uses_vx: false,
+            clip_sprites: true,
+            stack_depth: Some(12),
+            key_wait_on_release: true,
+        }
+    }
+
+    // CHIP-48 on the HP-48 calculators
+    pub fn chip48() -> Self {
+        Self {
+            instruction_set: InstructionSet::Chip8,
+            shift_uses_vy: false,
+            load_store: LoadStoreIndex::IncrementByX,
+            logic_resets_vf: false,
+            jump_uses_vx: true,
+            clip_sprites: true,
+            stack_depth: Some(16),
+            key_wait_on_release: true,
+        }
+    }
+
+    // SUPER-CHIP 1.1
+    pub fn superchip() -> Self {
+        Self {
+            instruction_set: InstructionSet::SuperChip,
+            shift_uses_vy: false,
+            load_store: LoadStoreIndex::Unchanged,
+            logic_resets_vf: false,
+            jump_uses_vx: true,
+            clip_sprites: true,
+            stack_depth: Some(16),
+            key_wait_on_release: true,
+        }
+    }
+
+    // What most modern emulators (and Cowgod's technical reference) do, SUPER-CHIP opcodes included
+    pub fn modern() -> Self {
+        Self {
+            instruction_set: InstructionSet::SuperChip,
+            shift_uses_vy: false,
+            load_store: LoadStoreIndex::Unchanged,
+            logic_resets_vf: false,
+            jump_uses_vx: false,
+            clip_sprites: true,
+            stack_depth: Some(16),
+            key_wait_on_release: true,
+        }
+    }
+
+    // XO-CHIP as implemented by Octo
+    pub fn xochip() -> Self {
+        Self {
+            instruction_set: InstructionSet::XoChip,
+            shift_uses_vy: true,
+            load_store: LoadStoreIndex::IncrementByXPlusOne,
+            logic_resets_vf: false,
+            jump_uses_vx: false,
+            clip_sprites: false,
+            stack_depth: Some(16),
+            key_wait_on_release: true,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::modern()
+    }
+}
+
+// Named presets so a profile can be picked by name (e.g. from the command line)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+    CosmacVip,
+    Chip48,
+    SuperChip,
+    XoChip,
+    Modern,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 5] = [
+        Platform::CosmacVip,
+        Platform::Chip48,
+        Platform::SuperChip,
+        Platform::XoChip,
+        Platform::Modern,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::CosmacVip => "vip",
+            Platform::Chip48 => "chip48",
+            Platform::SuperChip => "schip",
+            Platform::XoChip => "xochip",
+            Platform::Modern => "modern",
+        }
+    }
+
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::CosmacVip => Quirks::cosmac_vip(),
+            Platform::Chip48 => Quirks::chip48(),
+            Platform::SuperChip => Quirks::superchip(),
+            Platform::XoChip => Quirks::xochip(),
+            Platform::Modern => Quirks::modern(),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vip" | "cosmac-vip" | "cosmac" | "chip8" | "chip-8" => Ok(Platform::CosmacVip),
+            "chip48" | "chip-48" => Ok(Platform::Chip48),
+            "schip" | "superchip" | "super-chip" => Ok(Platform::SuperChip),
+            "xochip" | "xo-chip" => Ok(Platform::XoChip),
+            "modern" => Ok(Platform::Modern),
+            _ => {
+                let names: Vec<&str> = Platform::ALL.iter().map(|p| p.name()).collect();
+                Err(format!("unknown quirks profile '{}', expected one of: {}", s, names.join(", ")))
+            }
+        }
+    }
+}
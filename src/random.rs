@@ -0,0 +1,30 @@
+// Random numbers for Cxnn. xorshift64* is plenty for games and, unlike thread_rng, its state
+// can go into snapshots and save states so a restored game rolls the same numbers.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    // The same seed always gives the same sequence, 0 is replaced since xorshift would stay at 0
+    pub fn new(seed: u64) -> Self {
+        Self { state: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed } }
+    }
+
+    // Seeded from the operating system, for normal play
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+}
@@ -0,0 +1,102 @@
+// Rewind: a ring buffer of snapshots taken every few frames. Stepping back restores them newest
+// first, one per frame, so holding the rewind key plays the game backwards.
+use std::collections::VecDeque;
+use crate::clock::TIMER_HZ;
+use crate::processor::Processor;
+use crate::snapshot::Snapshot;
+
+// frames between snapshots, rewinding plays back at this many times normal speed
+pub const DEFAULT_INTERVAL: u32 = 2;
+pub const DEFAULT_SECONDS: u32 = 60;
+// a snapshot holds all of RAM, so the same seconds cost about 14 times as much with XO-CHIP's 64K
+// as with CHIP-8's 4K. A minute of XO-CHIP fits under this, anything longer is cut short by it.
+pub const DEFAULT_MAX_BYTES: usize = 128 * 1024 * 1024;
+
+pub struct Rewind {
+    snapshots: VecDeque<Snapshot>,
+    // snapshots that make up the seconds asked for
+    capacity: usize,
+    max_bytes: usize,
+    // total Snapshot::size of the snapshots kept
+    bytes: usize,
+    interval: u32,
+    // frames since the last snapshot
+    frames: u32,
+}
+
+impl Rewind {
+    // Keep up to seconds of play, as long as it fits in max_bytes
+    pub fn new(seconds: u32, max_bytes: usize, interval: u32) -> Self {
+        let interval = interval.max(1);
+        Self {
+            snapshots: VecDeque::new(),
+            capacity: (seconds * TIMER_HZ / interval) as usize,
+            max_bytes,
+            bytes: 0,
+            interval,
+            frames: 0,
+        }
+    }
+
+    // Call once per frame while the game runs, the oldest snapshots go once the buffer is full
+    pub fn record(&mut self, processor: &Processor) {
+        self.frames += 1;
+        if self.frames < self.interval || self.capacity == 0 {
+            return;
+        }
+        self.frames = 0;
+
+        let snapshot = processor.snapshot();
+        self.bytes += snapshot.size();
+        self.snapshots.push_back(snapshot);
+        while self.snapshots.len() > self.capacity || self.bytes > self.max_bytes {
+            match self.snapshots.pop_front() {
+                Some(oldest) => self.bytes -= oldest.size(),
+                None => break,
+            }
+        }
+    }
+
+    // Go back to the newest snapshot, false when there is nothing left to rewind to
+    pub fn step_back(&mut self, processor: &mut Processor) -> bool {
+        match self.snapshots.pop_back() {
+            Some(snapshot) => {
+                self.bytes -= snapshot.size();
+                processor.restore(&snapshot);
+                self.frames = 0;
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.bytes = 0;
+        self.frames = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    // Seconds of play that can be rewound right now
+    pub fn seconds(&self) -> f64 {
+        (self.snapshots.len() as u32 * self.interval) as f64 / TIMER_HZ as f64
+    }
+
+    // Memory used by the snapshots in bytes
+    pub fn size(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Self::new(DEFAULT_SECONDS, DEFAULT_MAX_BYTES, DEFAULT_INTERVAL)
+    }
+}
@@ -0,0 +1,279 @@
+// Save states on disk. A file is a header followed by the snapshot, all integers little endian:
+//   magic "C8SS", format version (u16), hash of the ROM the state belongs to (u64), snapshot
+// The version is bumped whenever the snapshot layout changes, older files are refused rather than
+// misread. A state is only loaded back into the ROM it was saved from.
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::quirks::{InstructionSet, LoadStoreIndex, Quirks};
+use crate::snapshot::{PackedScreen, Snapshot};
+
+pub const MAGIC: [u8; 4] = *b"C8SS";
+pub const VERSION: u16 = 3;
+
+#[derive(Debug)]
+pub enum SaveStateError {
+    Io(io::Error),
+    // the file doesn't start with the magic bytes
+    NotASaveState,
+    UnsupportedVersion(u16),
+    // the state was saved from another ROM
+    RomMismatch,
+    // the file ends early or holds values that can't be right
+    Corrupt,
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveStateError::Io(error) => write!(f, "{}", error),
+            SaveStateError::NotASaveState => f.write_str("not a save state"),
+            SaveStateError::UnsupportedVersion(version) => write!(f, "save state version {} is not supported (expected {})", version, VERSION),
+            SaveStateError::RomMismatch => f.write_str("save state was made for a different ROM"),
+            SaveStateError::Corrupt => f.write_str("save state is corrupt"),
+        }
+    }
+}
+
+impl Error for SaveStateError {}
+
+impl From<io::Error> for SaveStateError {
+    fn from(error: io::Error) -> Self {
+        SaveStateError::Io(error)
+    }
+}
+
+// FNV-1a over the ROM, identifies which ROM a state belongs to
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3))
+}
+
+pub fn save<P: AsRef<Path>>(path: P, snapshot: &Snapshot, rom_hash: u64) -> Result<(), SaveStateError> {
+    fs::write(path, encode(snapshot, rom_hash))?;
+    Ok(())
+}
+
+pub fn load<P: AsRef<Path>>(path: P, rom_hash: u64) -> Result<Snapshot, SaveStateError> {
+    decode(&fs::read(path)?, rom_hash)
+}
+
+pub fn encode(snapshot: &Snapshot, rom_hash: u64) -> Vec<u8> {
+    let mut writer = Writer(Vec::with_capacity(snapshot.size() + 64));
+
+    writer.bytes(&MAGIC);
+    writer.u16(VERSION);
+    writer.u64(rom_hash);
+
+    writer.u32(snapshot.memory.len() as u32);
+    writer.bytes(&snapshot.memory);
+    writer.u16(snapshot.screen.width as u16);
+    writer.u16(snapshot.screen.height as u16);
+    writer.bytes(&snapshot.screen.pixels);
+    writer.u8(snapshot.planes);
+    writer.u16(snapshot.pc);
+    writer.u16(snapshot.index);
+    writer.u16(snapshot.opcode);
+    writer.bytes(&snapshot.registers);
+    // the stack pointer is the number of entries, a u32 since the stack can be unlimited
+    writer.u32(snapshot.stack.len() as u32);
+    for &address in &snapshot.stack {
+        writer.u16(address);
+    }
+    writer.u8(snapshot.waiting_for_key as u8);
+    writer.u8(snapshot.key_register);
+    writer.u16(snapshot.keys_pressed);
+    writer.u8(snapshot.delay_timer);
+    writer.u8(snapshot.sound_timer);
+    writer.u8(snapshot.exited as u8);
+    writer.bytes(&snapshot.rpl);
+    writer.bytes(&snapshot.audio_pattern);
+    writer.u8(snapshot.pitch);
+    writer.u64(snapshot.random);
+
+    let quirks = snapshot.quirks;
+    writer.u8(match quirks.instruction_set {
+        InstructionSet::Chip8 => 0,
+        InstructionSet::SuperChip => 1,
+        InstructionSet::XoChip => 2,
+    });
+    writer.u8(quirks.shift_uses_vy as u8);
+    writer.u8(match quirks.load_store {
+        LoadStoreIndex::Unchanged => 0,
+        LoadStoreIndex::IncrementByX => 1,
+        LoadStoreIndex::IncrementByXPlusOne => 2,
+    });
+    writer.u8(quirks.logic_resets_vf as u8);
+    writer.u8(quirks.jump_uses_vx as u8);
+    writer.u8(quirks.clip_sprites as u8);
+    // 0 means no limit
+    writer.u32(quirks.stack_depth.map_or(0, |depth| depth as u32));
+    writer.u8(quirks.key_wait_on_release as u8);
+
+    writer.0
+}
+
+pub fn decode(bytes: &[u8], rom_hash: u64) -> Result<Snapshot, SaveStateError> {
+    let mut reader = Reader { bytes, position: 0 };
+
+    if reader.bytes(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        return Err(SaveStateError::NotASaveState);
+    }
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(SaveStateError::UnsupportedVersion(version));
+    }
+    if reader.u64()? != rom_hash {
+        return Err(SaveStateError::RomMismatch);
+    }
+
+    let memory_size = reader.u32()? as usize;
+    if memory_size != crate::CHIP8_RAM_SIZE_BYTES && memory_size != crate::XOCHIP_RAM_SIZE_BYTES {
+        return Err(SaveStateError::Corrupt);
+    }
+    let memory = reader.bytes(memory_size)?.to_vec();
+
+    let width = reader.u16()? as usize;
+    let height = reader.u16()? as usize;
+    if width == 0 || height == 0 || width > crate::SCHIP_SCREEN_WIDTH || height > crate::SCHIP_SCREEN_HEIGHT {
+        return Err(SaveStateError::Corrupt);
+    }
+    let pixels = reader.bytes((width * height).div_ceil(4))?.to_vec();
+    let screen = PackedScreen { width, height, pixels };
+
+    let planes = reader.u8()?;
+    let pc = reader.u16()?;
+    let index = reader.u16()?;
+    let opcode = reader.u16()?;
+    let registers = reader.array()?;
+    let stack_pointer = reader.u32()?;
+    let stack = (0..stack_pointer).map(|_| reader.u16()).collect::<Result<Vec<u16>, _>>()?;
+    let waiting_for_key = reader.bool()?;
+    let key_register = reader.u8()?;
+    let keys_pressed = reader.u16()?;
+    let delay_timer = reader.u8()?;
+    let sound_timer = reader.u8()?;
+    let exited = reader.bool()?;
+    let rpl = reader.array()?;
+    let audio_pattern = reader.array()?;
+    let pitch = reader.u8()?;
+    let random = reader.u64()?;
+
+    let instruction_set = match reader.u8()? {
+        0 => InstructionSet::Chip8,
+        1 => InstructionSet::SuperChip,
+        2 => InstructionSet::XoChip,
+        _ => return Err(SaveStateError::Corrupt),
+    };
+    let shift_uses_vy = reader.bool()?;
+    let load_store = match reader.u8()? {
+        0 => LoadStoreIndex::Unchanged,
+        1 => LoadStoreIndex::IncrementByX,
+        2 => LoadStoreIndex::IncrementByXPlusOne,
+        _ => return Err(SaveStateError::Corrupt),
+    };
+    let logic_resets_vf = reader.bool()?;
+    let jump_uses_vx = reader.bool()?;
+    let clip_sprites = reader.bool()?;
+    let stack_depth = match reader.u32()? {
+        0 => None,
+        depth => Some(depth as usize),
+    };
+    let key_wait_on_release = reader.bool()?;
+    let quirks = Quirks { instruction_set, shift_uses_vy, load_store, logic_resets_vf, jump_uses_vx, clip_sprites, stack_depth, key_wait_on_release };
+
+    if key_register > 0xF || reader.position != bytes.len() {
+        return Err(SaveStateError::Corrupt);
+    }
+
+    Ok(Snapshot {
+        memory,
+        screen,
+        planes,
+        pc,
+        index,
+        opcode,
+        registers,
+        stack,
+        waiting_for_key,
+        key_register,
+        keys_pressed,
+        delay_timer,
+        sound_timer,
+        exited,
+        rpl,
+        audio_pattern,
+        pitch,
+        random,
+        quirks,
+    })
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], SaveStateError> {
+        let end = self.position.checked_add(length).filter(|&end| end <= self.bytes.len()).ok_or(SaveStateError::Corrupt)?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SaveStateError> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, SaveStateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SaveStateError::Corrupt),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+}
@@ -0,0 +1,115 @@
+// Decides how many 60Hz frames to emulate each time round the frontend's loop, from the time
+// that has passed. The loop can then run at whatever rate the display refreshes (present waits
+// for vsync) and the game still runs at 60 frames a second, or faster or slower on purpose.
+use std::time::{Duration, Instant};
+use crate::clock::TIMER_HZ;
+
+pub const FAST_FORWARD: f64 = 2.0;
+pub const FAST_FORWARD_MORE: f64 = 4.0;
+pub const SLOW_MOTION: f64 = 0.25;
+
+// After a stall (the window being dragged, a breakpoint) the missed time is dropped instead of
+// emulated all at once
+const MAX_FRAMES_PER_UPDATE: u32 = 8;
+// A frame that's due this close to now runs now, so a 60Hz display whose vsync lands a little
+// early doesn't alternate between 0 and 2 frames per refresh
+const SLACK: Duration = Duration::from_millis(1);
+
+pub struct Scheduler {
+    frame: Duration,
+    // 1.0 is real time
+    speed: f64,
+    // time owed to the emulation that doesn't make up a whole frame yet, already scaled by speed
+    owed: Duration,
+    last_update: Instant,
+    paused: bool,
+    // frames to run while paused, one per frame advance
+    advance: u32,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            frame: Duration::from_secs(1) / TIMER_HZ,
+            speed: 1.0,
+            owed: Duration::ZERO,
+            last_update: Instant::now(),
+            paused: false,
+            advance: 0,
+        }
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.advance = 0;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.paused {
+            self.resume();
+        }
+        else {
+            self.pause();
+        }
+    }
+
+    // Run a single frame next update, only while paused
+    pub fn advance_frame(&mut self) {
+        if self.paused {
+            self.advance += 1;
+        }
+    }
+
+    // How many frames to emulate now, call once per loop
+    pub fn update(&mut self) -> u32 {
+        let now = Instant::now();
+        let elapsed = now - self.last_update;
+        self.last_update = now;
+
+        if self.paused {
+            // resuming carries on from here rather than catching up on the pause
+            self.owed = Duration::ZERO;
+            return std::mem::take(&mut self.advance);
+        }
+
+        self.owed += elapsed.mul_f64(self.speed);
+        let frames = ((self.owed + SLACK).as_nanos() / self.frame.as_nanos()) as u32;
+        if frames > MAX_FRAMES_PER_UPDATE {
+            self.owed = Duration::ZERO;
+            return MAX_FRAMES_PER_UPDATE;
+        }
+
+        self.owed = self.owed.saturating_sub(self.frame * frames);
+        frames
+    }
+
+    // How long until the next frame is due, for sleeping when present doesn't wait for vsync
+    pub fn time_to_next_frame(&self) -> Duration {
+        if self.paused {
+            return self.frame;
+        }
+        self.frame.saturating_sub(self.owed).div_f64(self.speed).saturating_sub(self.last_update.elapsed())
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,67 @@
+// A copy of everything a running ROM can change, taken with Processor::snapshot and put back with
+// Processor::restore. Settings that belong to the frontend (clock rate, error and memory policies,
+// the tracer) aren't part of it.
+use crate::framebuffer::FrameBuffer;
+use crate::quirks::Quirks;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub(crate) memory: Vec<u8>,
+    pub(crate) screen: PackedScreen,
+    pub(crate) planes: u8,
+    pub(crate) pc: u16,
+    pub(crate) index: u16,
+    pub(crate) opcode: u16,
+    pub(crate) registers: [u8; 16],
+    pub(crate) stack: Vec<u16>,
+    pub(crate) waiting_for_key: bool,
+    pub(crate) key_register: u8,
+    // keys pressed while Fx0A waits, bit n for key n
+    pub(crate) keys_pressed: u16,
+    pub(crate) delay_timer: u8,
+    pub(crate) sound_timer: u8,
+    pub(crate) exited: bool,
+    pub(crate) rpl: [u8; 16],
+    pub(crate) audio_pattern: [u8; 16],
+    pub(crate) pitch: u8,
+    // state of the Cxnn random number generator
+    pub(crate) random: u64,
+    pub(crate) quirks: Quirks,
+}
+
+impl Snapshot {
+    // Rough size in bytes, most of it is RAM
+    pub fn size(&self) -> usize {
+        self.memory.len() + self.screen.pixels.len() + self.stack.len() * 2 + std::mem::size_of::<Self>()
+    }
+}
+
+// The framebuffer with four 2-bit pixels per byte, a lores screen is 512 bytes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PackedScreen {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) pixels: Vec<u8>,
+}
+
+impl PackedScreen {
+    pub(crate) fn pack(framebuffer: &FrameBuffer) -> Self {
+        let mut pixels = vec![0u8; (framebuffer.width() * framebuffer.height()).div_ceil(4)];
+        for (i, &pixel) in framebuffer.as_slice().iter().enumerate() {
+            pixels[i / 4] |= pixel << ((i % 4) * 2);
+        }
+
+        Self { width: framebuffer.width(), height: framebuffer.height(), pixels }
+    }
+
+    pub(crate) fn unpack(&self) -> FrameBuffer {
+        let mut framebuffer = FrameBuffer::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = y * self.width + x;
+                framebuffer.set_pixel(x, y, self.pixels[i / 4] >> ((i % 4) * 2));
+            }
+        }
+        framebuffer
+    }
+}
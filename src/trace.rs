@@ -0,0 +1,122 @@
+// Execution trace, one line per instruction with the machine state before it runs:
+//   cycle    PC   opcode mnemonic             V0-VF                                           I    SP DT ST
+//   00000042 0204 7101   ADD V1, 0x01         00 01 02 00 00 00 00 00 00 00 00 00 00 00 00 00 0000 0  00 00
+// The columns have a fixed width so traces from two runs (or other emulators) can be diffed.
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use crate::instruction::Instruction;
+use crate::processor::Processor;
+
+pub struct Tracer {
+    output: Box<dyn Write>,
+    // only instructions at these addresses are written, every address when empty
+    ranges: Vec<RangeInclusive<u16>>,
+    max_lines: Option<usize>,
+    lines: usize,
+    // instructions executed so far, traced or not
+    cycles: u64,
+    // set once the output failed, so a full disk doesn't stop the emulator
+    failed: bool,
+}
+
+impl Tracer {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            output,
+            ranges: Vec::new(),
+            max_lines: None,
+            lines: 0,
+            cycles: 0,
+            failed: false,
+        }
+    }
+
+    pub fn to_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(Box::new(BufWriter::new(File::create(path)?))))
+    }
+
+    // Only trace instructions between start and end (inclusive), can be called more than once
+    pub fn add_range(&mut self, start: u16, end: u16) {
+        self.ranges.push(start..=end);
+    }
+
+    // Stop writing after this many lines
+    pub fn set_max_lines(&mut self, max_lines: Option<usize>) {
+        self.max_lines = max_lines;
+    }
+
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    // Whether the max lines cap has been reached
+    pub fn is_full(&self) -> bool {
+        self.max_lines.is_some_and(|max_lines| self.lines >= max_lines)
+    }
+
+    // Called with the instruction at the pc, before it executes
+    pub fn record(&mut self, processor: &Processor, instruction: Instruction) {
+        self.cycles += 1;
+
+        let pc = processor.pc();
+        if self.failed || self.is_full() {
+            return;
+        }
+        if !self.ranges.is_empty() && !self.ranges.iter().any(|range| range.contains(&pc)) {
+            return;
+        }
+
+        let registers: Vec<String> = processor.registers().iter().map(|value| format!("{:02X}", value)).collect();
+        let result = writeln!(self.output, "{:08} {:04X} {:04X}   {:<20} {} {:04X} {:<2X} {:02X} {:02X}",
+            self.cycles,
+            pc,
+            instruction.opcode(),
+            instruction.to_string(),
+            registers.join(" "),
+            processor.index(),
+            processor.stack().len(),
+            processor.delay_timer(),
+            processor.sound_timer());
+
+        match result {
+            Ok(()) => self.lines += 1,
+            Err(_) => self.failed = true,
+        }
+        if self.is_full() {
+            self.flush();
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if self.output.flush().is_err() {
+            self.failed = true;
+        }
+    }
+}
+
+// A range of hex addresses like 200-2ff, either end can have a 0x
+pub fn parse_range(text: &str) -> Result<(u16, u16), String> {
+    let parse_address = |address: &str| u16::from_str_radix(address.trim_start_matches("0x"), 16).ok();
+    match text.split_once('-').map(|(start, end)| (parse_address(start), parse_address(end))) {
+        Some((Some(start), Some(end))) if start <= end => Ok((start, end)),
+        Some((Some(_), Some(_))) => Err(format!("start must not be past its end, got {}", text)),
+        _ => Err(format!("expects <start>-<end> in hex, got '{}'", text)),
+    }
+}
+
+// A line cap of at least 1, a cap of 0 would trace nothing
+pub fn parse_max_lines(text: &str) -> Result<usize, String> {
+    match text.parse() {
+        Ok(0) => Err(String::from("must be at least 1, got 0")),
+        Ok(max_lines) => Ok(max_lines),
+        Err(_) => Err(format!("expects a number, got '{}'", text)),
+    }
+}
+
+impl Drop for Tracer {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
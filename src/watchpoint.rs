@@ -0,0 +1,289 @@
+// Watchpoints on RAM and registers. RAM accesses come from the memory's access tracking, so reads
+// made by Dxyn, Fx65 etc. are caught too. Registers, I and the timers trigger on every instruction
+// that writes them, even with the value they already had, and the timers on every tick.
+use std::fmt;
+use crate::instruction::Instruction;
+use crate::memory::{Access, AccessKind};
+use crate::processor::Processor;
+use crate::quirks::{LoadStoreIndex, Quirks};
+
+// Which RAM accesses a memory watchpoint reacts to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Trigger {
+    fn matches(self, kind: AccessKind) -> bool {
+        match self {
+            Trigger::Read => kind == AccessKind::Read,
+            Trigger::Write => kind == AccessKind::Write,
+            Trigger::ReadWrite => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    // length bytes starting at address
+    Memory { address: usize, length: usize, trigger: Trigger },
+    Register(usize),
+    Index,
+    DelayTimer,
+    SoundTimer,
+}
+
+impl Target {
+    // Current value of a register target, None for memory
+    fn value(self, processor: &Processor) -> Option<u16> {
+        match self {
+            Target::Memory { .. } => None,
+            Target::Register(x) => Some(processor.registers()[x] as u16),
+            Target::Index => Some(processor.index()),
+            Target::DelayTimer => Some(processor.delay_timer() as u16),
+            Target::SoundTimer => Some(processor.sound_timer() as u16),
+        }
+    }
+
+    // Whether executing the instruction writes this target, whatever value it writes
+    fn written_by(self, instruction: Instruction, quirks: Quirks) -> bool {
+        match self {
+            Target::Memory { .. } => false,
+            Target::Register(r) => match instruction {
+                Instruction::Load(x, _)
+                | Instruction::Add(x, _)
+                | Instruction::Move(x, _)
+                | Instruction::Random(x, _)
+                | Instruction::LoadDelay(x) => r == x as usize,
+                Instruction::Or(x, _)
+                | Instruction::And(x, _)
+                | Instruction::Xor(x, _) => r == x as usize || (r == 0xF && quirks.logic_resets_vf),
+                Instruction::AddRegisters(x, _)
+                | Instruction::Sub(x, _)
+                | Instruction::ShiftRight(x, _)
+                | Instruction::SubReverse(x, _)
+                | Instruction::ShiftLeft(x, _) => r == x as usize || r == 0xF,
+                Instruction::Draw(_, _, _) => r == 0xF,
+                Instruction::LoadRegisters(x) | Instruction::LoadFlags(x) => r <= x as usize,
+                Instruction::LoadRange(x, y) => (x.min(y) as usize..=x.max(y) as usize).contains(&r),
+                _ => false,
+            },
+            Target::Index => match instruction {
+                Instruction::LoadIndex(_)
+                | Instruction::LoadIndexLong(_)
+                | Instruction::AddIndex(_)
+                | Instruction::LoadFont(_)
+                | Instruction::LoadBigFont(_) => true,
+                Instruction::StoreRegisters(_) | Instruction::LoadRegisters(_) => quirks.load_store != LoadStoreIndex::Unchanged,
+                _ => false,
+            },
+            Target::DelayTimer => matches!(instruction, Instruction::SetDelay(_)),
+            Target::SoundTimer => matches!(instruction, Instruction::SetSound(_)),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Target::Memory { address, length, trigger } => {
+                let trigger = match trigger {
+                    Trigger::Read => "read",
+                    Trigger::Write => "write",
+                    Trigger::ReadWrite => "read/write",
+                };
+                write!(f, "{:03X}-{:03X} ({})", address, address.saturating_add(length.max(1) - 1), trigger)
+            },
+            Target::Register(x) => write!(f, "V{:X}", x),
+            Target::Index => f.write_str("I"),
+            Target::DelayTimer => f.write_str("DT"),
+            Target::SoundTimer => f.write_str("ST"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HitKind {
+    Memory(Access),
+    // an instruction wrote the register, new can be the same as old
+    Written { old: u16, new: u16 },
+    // a timer counted down
+    Ticked { old: u16, new: u16 },
+}
+
+// A watchpoint that triggered, pc is the address of the instruction responsible
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hit {
+    pub id: usize,
+    pub pc: u16,
+    pub target: Target,
+    pub kind: HitKind,
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            HitKind::Memory(access) => {
+                let kind = match access.kind {
+                    AccessKind::Read => "read from",
+                    AccessKind::Write => "write to",
+                };
+                write!(f, "watchpoint {}: {} {:03X} at {:03X}", self.id, kind, access.address, self.pc)
+            },
+            HitKind::Written { old, new } => {
+                write!(f, "watchpoint {}: {} written {:02X} -> {:02X} at {:03X}", self.id, self.target, old, new, self.pc)
+            },
+            HitKind::Ticked { old, new } => {
+                write!(f, "watchpoint {}: {} ticked {:02X} -> {:02X}", self.id, self.target, old, new)
+            },
+        }
+    }
+}
+
+pub type Callback = Box<dyn FnMut(&Hit)>;
+
+struct Watchpoint {
+    id: usize,
+    target: Target,
+    // watchpoints without a callback pause execution
+    callback: Option<Callback>,
+    // value before the current instruction, for register targets
+    previous: Option<u16>,
+}
+
+impl Watchpoint {
+    // Fire the callback or add the hit to the ones that pause
+    fn hit(&mut self, pc: u16, kind: HitKind, pauses: &mut Vec<Hit>) {
+        let hit = Hit { id: self.id, pc, target: self.target, kind };
+        match self.callback.as_mut() {
+            Some(callback) => callback(&hit),
+            None => pauses.push(hit),
+        }
+    }
+}
+
+pub struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    next_id: usize,
+    pc: u16,
+    // the instruction about to run, None when nothing will run or it can't be read
+    instruction: Option<Instruction>,
+}
+
+impl Watchpoints {
+    pub fn new() -> Self {
+        Self {
+            watchpoints: Vec::new(),
+            next_id: 1,
+            pc: 0,
+            instruction: None,
+        }
+    }
+
+    // Watch a target and pause when it's hit, returns the watchpoint's id
+    pub fn add(&mut self, target: Target) -> usize {
+        self.insert(target, None)
+    }
+
+    // Watch a target and call back when it's hit, execution carries on
+    pub fn add_with_callback(&mut self, target: Target, callback: Callback) -> usize {
+        self.insert(target, Some(callback))
+    }
+
+    fn insert(&mut self, target: Target, callback: Option<Callback>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.watchpoints.push(Watchpoint { id, target, callback, previous: None });
+        id
+    }
+
+    pub fn remove(&mut self, id: usize) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|watchpoint| watchpoint.id != id);
+        self.watchpoints.len() != count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, Target)> + '_ {
+        self.watchpoints.iter().map(|watchpoint| (watchpoint.id, watchpoint.target))
+    }
+
+    // Call before every instruction: remembers the watched values and turns on memory tracking
+    // only while there is a memory watchpoint
+    pub fn before(&mut self, processor: &mut Processor) {
+        self.pc = processor.pc();
+        // nothing runs while Fx0A waits for a key or after 00FD
+        self.instruction = if processor.waiting_for_key() || processor.exited() { None } else { processor.read_instruction(self.pc).ok() };
+        for watchpoint in self.watchpoints.iter_mut() {
+            watchpoint.previous = watchpoint.target.value(processor);
+        }
+
+        let tracking = self.watchpoints.iter().any(|watchpoint| matches!(watchpoint.target, Target::Memory { .. }));
+        let memory = processor.memory_mut();
+        memory.set_tracking(tracking);
+        memory.take_accesses();
+    }
+
+    // Call after an instruction that ran without faulting: fires callbacks and returns the hits
+    // that should pause
+    pub fn after(&mut self, processor: &mut Processor) -> Vec<Hit> {
+        let accesses = processor.memory_mut().take_accesses();
+        let quirks = processor.quirks();
+        let mut pauses = Vec::new();
+
+        for watchpoint in self.watchpoints.iter_mut() {
+            match watchpoint.target {
+                Target::Memory { address, length, trigger } => {
+                    let range = address..address.saturating_add(length.max(1));
+                    for access in accesses.iter().filter(|access| range.contains(&access.address) && trigger.matches(access.kind)) {
+                        watchpoint.hit(self.pc, HitKind::Memory(*access), &mut pauses);
+                    }
+                },
+                target => {
+                    // a change without a write is Fx0A finishing while it waits for a key
+                    let written = self.instruction.is_some_and(|instruction| target.written_by(instruction, quirks));
+                    if let (Some(old), Some(new)) = (watchpoint.previous, target.value(processor)) {
+                        if written || old != new {
+                            watchpoint.hit(self.pc, HitKind::Written { old, new }, &mut pauses);
+                        }
+                    }
+                },
+            }
+        }
+
+        pauses
+    }
+
+    // Tick the processor's timers in place of Processor::tick_timers, reporting DT and ST
+    // watchpoints the tick changed
+    pub fn tick_timers(&mut self, processor: &mut Processor) -> Vec<Hit> {
+        let timers = |watchpoint: &&mut Watchpoint| matches!(watchpoint.target, Target::DelayTimer | Target::SoundTimer);
+        for watchpoint in self.watchpoints.iter_mut().filter(timers) {
+            watchpoint.previous = watchpoint.target.value(processor);
+        }
+        processor.tick_timers();
+
+        let pc = processor.pc();
+        let mut pauses = Vec::new();
+        for watchpoint in self.watchpoints.iter_mut().filter(timers) {
+            if let (Some(old), Some(new)) = (watchpoint.previous, watchpoint.target.value(processor)) {
+                if old != new {
+                    watchpoint.hit(pc, HitKind::Ticked { old, new }, &mut pauses);
+                }
+            }
+        }
+
+        pauses
+    }
+}
+
+impl Default for Watchpoints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
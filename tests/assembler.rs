@@ -0,0 +1,108 @@
+// Octo source in, ROM bytes out. The expected bytes are written as opcodes, see Instruction for
+// what each one is.
+use chip8_emulator_rust::{CHIP8_START_OF_PROGRAM, XOCHIP_PROGRAM_SIZE, assembler::assemble, processor::Processor, quirks::Quirks};
+
+fn words(rom: &[u8]) -> Vec<u16> {
+    rom.chunks(2).map(|pair| (pair[0] as u16) << 8 | *pair.get(1).unwrap_or(&0) as u16).collect()
+}
+
+fn assemble_words(source: &str) -> Vec<u16> {
+    words(&assemble(source).unwrap_or_else(|error| panic!("{}", error)))
+}
+
+#[test]
+fn main_first_needs_no_jump() {
+    assert_eq!(assemble_words(": main clear"), [0x00E0]);
+}
+
+#[test]
+fn jumps_to_main_past_data_and_subroutines() {
+    // smile is at 202, main at 204
+    assert_eq!(assemble_words(": smile 0x24 0x24 : main i := smile"), [0x1204, 0x2424, 0xA202]);
+}
+
+#[test]
+fn missing_main_is_an_error() {
+    let error = assemble(": f ;").unwrap_err();
+    assert!(error.message.contains("main"), "{}", error);
+}
+
+#[test]
+fn calls_a_subroutine_defined_earlier() {
+    assert_eq!(assemble_words(": f ; : main f"), [0x1204, 0x00EE, 0x2202]);
+    assert_eq!(assemble_words(": draw-it clear ; : main draw-it draw-it"), [0x1206, 0x00E0, 0x00EE, 0x2202, 0x2202]);
+}
+
+#[test]
+fn calls_a_subroutine_defined_later() {
+    assert_eq!(assemble_words(": main f ; : f clear ;"), [0x2204, 0x00EE, 0x00E0, 0x00EE]);
+}
+
+#[test]
+fn numbers_and_constants_are_data() {
+    assert_eq!(assemble(":const SIZE 8 : main ; 1 0x2 SIZE").unwrap(), [0x00, 0xEE, 0x01, 0x02, 0x08]);
+}
+
+#[test]
+fn if_then() {
+    // skip the statement when v1 isn't 2
+    assert_eq!(assemble_words(": main if v1 == 2 then v2 := 3"), [0x4102, 0x6203]);
+}
+
+#[test]
+fn if_begin_else_end() {
+    assert_eq!(assemble_words(": main if v1 != v2 begin v3 := 1 else v3 := 2 end"),
+        [0x9120, 0x1208, 0x6301, 0x120A, 0x6302]);
+    assert_eq!(assemble_words(": main if v1 key begin v3 := 1 end"), [0xE19E, 0x1206, 0x6301]);
+}
+
+#[test]
+fn loop_while_again() {
+    // the while jumps past the again
+    assert_eq!(assemble_words(": main loop v0 += 1 while v0 != 5 again"), [0x7001, 0x4005, 0x1208, 0x1200]);
+}
+
+#[test]
+fn unclosed_blocks_are_errors() {
+    assert!(assemble(": main loop v0 += 1").is_err());
+    assert!(assemble(": main if v0 == 1 begin v0 += 1").is_err());
+    assert!(assemble(": main again").is_err());
+}
+
+#[test]
+fn less_than_goes_through_vf() {
+    // vf := 5, vf =- v1 leaves vf set when v1 >= 5
+    assert_eq!(assemble_words(": main if v1 < 5 then v2 := 1"), [0x6F05, 0x8F17, 0x4F00, 0x6201]);
+}
+
+#[test]
+fn greater_than_goes_through_vf() {
+    // vf := v2, vf -= v1 leaves vf set when v2 >= v1
+    assert_eq!(assemble_words(": main if v1 > v2 then v3 := 1"), [0x8F20, 0x8F15, 0x4F00, 0x6301]);
+}
+
+// Run the comparisons on the processor, v2 ends up 1 when the comparison holds
+#[test]
+fn comparisons_run_correctly() {
+    for operator in ["<", ">", "<=", ">="] {
+        for a in [0u8, 4, 5, 6, 255] {
+            let source = format!(": main v1 := {} v2 := 0 if v1 {} 5 then v2 := 1 : halt jump halt", a, operator);
+            let rom = assemble(&source).unwrap();
+            let mut program = [0u8; XOCHIP_PROGRAM_SIZE];
+            program[..rom.len()].copy_from_slice(&rom);
+
+            let mut processor = Processor::new(Quirks::default());
+            processor.load(program, rom.len(), CHIP8_START_OF_PROGRAM);
+            for _ in 0..10 {
+                processor.cycle([false; 16]).unwrap();
+            }
+            let holds = match operator {
+                "<" => a < 5,
+                ">" => a > 5,
+                "<=" => a <= 5,
+                _ => a >= 5,
+            };
+            assert_eq!(processor.registers()[2] == 1, holds, "{} {} 5", a, operator);
+        }
+    }
+}
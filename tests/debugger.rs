@@ -0,0 +1,92 @@
+// The debugger is driven through run_frame and execute with in-memory input and output, the way
+// the frontend drives it with stdin and stdout.
+use chip8_emulator_rust::{CHIP8_START_OF_PROGRAM, XOCHIP_PROGRAM_SIZE, debugger::{Command, Debugger}, error::ErrorPolicy, processor::Processor, quirks::Quirks};
+
+fn processor(quirks: Quirks, opcodes: &[u16]) -> Processor {
+    let mut program = [0u8; XOCHIP_PROGRAM_SIZE];
+    for (i, opcode) in opcodes.iter().enumerate() {
+        program[i * 2..i * 2 + 2].copy_from_slice(&opcode.to_be_bytes());
+    }
+
+    let mut processor = Processor::new(quirks);
+    processor.load(program, opcodes.len() * 2, CHIP8_START_OF_PROGRAM);
+    processor
+}
+
+// Run frames until the debugger quits, with input as the lines typed at the prompt
+fn run(debugger: &mut Debugger, processor: &mut Processor, input: &str) -> String {
+    let mut input = input.as_bytes();
+    let mut output = Vec::new();
+    for _ in 0..10 {
+        if !debugger.run_frame(processor, [false; 16], &mut input, &mut output) {
+            break;
+        }
+    }
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn fault_drops_into_the_prompt() {
+    // v0 := 1, return with nothing on the stack
+    let mut processor = processor(Quirks::modern(), &[0x6001, 0x00EE]);
+    processor.set_error_policy(ErrorPolicy::Halt);
+    let mut debugger = Debugger::new();
+
+    let output = run(&mut debugger, &mut processor, "regs\nquit\n");
+    assert!(output.starts_with("return with an empty stack at 202 (opcode 00EE)\n"), "{}", output);
+    assert!(output.contains("(chip8) V0=01"), "{}", output);
+    assert_eq!(processor.pc(), 0x202);
+}
+
+#[test]
+fn continue_after_a_fault_runs_from_the_new_pc() {
+    // return with nothing on the stack, then v0 := 1 and loop
+    let mut processor = processor(Quirks::modern(), &[0x00EE, 0x6001, 0x1204]);
+    processor.set_error_policy(ErrorPolicy::Halt);
+    let mut debugger = Debugger::new();
+
+    let output = run(&mut debugger, &mut processor, "set pc 0x202\ncontinue\n");
+    assert!(processor.halted().is_none(), "{}", output);
+    assert_eq!(processor.registers()[0], 1);
+}
+
+#[test]
+fn breakpoints_stop_before_the_instruction() {
+    let mut processor = processor(Quirks::modern(), &[0x6001, 0x6102, 0x1204]);
+    let mut debugger = Debugger::new();
+    debugger.add_breakpoint(0x202);
+
+    run(&mut debugger, &mut processor, "quit\n");
+    assert_eq!(processor.pc(), 0x202);
+    assert_eq!(processor.registers()[..2], [1, 0]);
+}
+
+fn disasm(processor: &mut Processor, count: usize) -> Vec<String> {
+    let mut output = Vec::new();
+    Debugger::new().execute(Command::Disasm(count), processor, &mut output).unwrap();
+    String::from_utf8(output).unwrap().lines().map(String::from).collect()
+}
+
+#[test]
+fn disasm_stops_at_the_ends_of_memory() {
+    let mut processor = processor(Quirks::xochip(), &[]);
+
+    processor.set_pc(0x0002);
+    let lines = disasm(&mut processor, 4);
+    assert_eq!(lines.len(), 6);
+    assert!(lines[0].starts_with("   000:"), "{:?}", lines);
+    assert!(lines[1].starts_with("=> 002:"), "{:?}", lines);
+
+    processor.set_pc(0xFFFC);
+    let lines = disasm(&mut processor, 4);
+    assert_eq!(lines.len(), 6);
+    assert!(lines.last().unwrap().starts_with("   FFFE:"), "{:?}", lines);
+}
+
+#[test]
+fn disasm_with_a_huge_count_saturates() {
+    let mut processor = processor(Quirks::xochip(), &[]);
+    processor.set_pc(0x8000);
+    assert_eq!(disasm(&mut processor, usize::MAX).len(), 0x8000);
+    assert_eq!(Command::parse("disasm 40000"), Ok(Command::Disasm(40000)));
+}
@@ -0,0 +1,211 @@
+// The GDB stub over an in-memory connection. Each test scripts what the client sends and checks
+// what the stub wrote back, packets are framed as $data#checksum.
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::rc::Rc;
+use chip8_emulator_rust::{CHIP8_START_OF_PROGRAM, XOCHIP_PROGRAM_SIZE, error::ErrorPolicy, gdbstub::{Connection, GdbStub, Session}, memory::MemoryPolicy, processor::Processor, quirks::Quirks};
+
+// Input arrives in chunks, a non-blocking read only sees the next chunk. Reading past the end
+// of the input is the client disconnecting.
+struct Client {
+    chunks: VecDeque<VecDeque<u8>>,
+    output: Rc<RefCell<Vec<u8>>>,
+    nonblocking: Cell<bool>,
+}
+
+impl Read for Client {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while self.chunks.front().is_some_and(|chunk| chunk.is_empty()) {
+            self.chunks.pop_front();
+        }
+        match self.chunks.front_mut() {
+            Some(chunk) => {
+                let count = buffer.len().min(chunk.len());
+                for (byte, value) in buffer.iter_mut().zip(chunk.drain(..count)) {
+                    *byte = value;
+                }
+                Ok(count)
+            },
+            None if self.nonblocking.get() => Err(io::Error::from(ErrorKind::WouldBlock)),
+            None => Ok(0),
+        }
+    }
+}
+
+impl Write for Client {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.output.borrow_mut().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Connection for Client {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.nonblocking.set(nonblocking);
+        Ok(())
+    }
+}
+
+fn packet(data: &str) -> String {
+    let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+    format!("${}#{:02x}", data, checksum)
+}
+
+// Turns off acknowledgements so the rest of the script is just packets
+fn no_ack() -> String {
+    packet("QStartNoAckMode") + "+"
+}
+
+fn processor(quirks: Quirks, opcodes: &[u16]) -> Processor {
+    let mut program = [0u8; XOCHIP_PROGRAM_SIZE];
+    for (i, opcode) in opcodes.iter().enumerate() {
+        program[i * 2..i * 2 + 2].copy_from_slice(&opcode.to_be_bytes());
+    }
+
+    let mut processor = Processor::new(quirks);
+    processor.set_error_policy(ErrorPolicy::Halt);
+    processor.load(program, opcodes.len() * 2, CHIP8_START_OF_PROGRAM);
+    processor
+}
+
+// Run frames until the client goes away, returns what the stub sent
+fn session(processor: &mut Processor, chunks: &[&str]) -> (String, Session) {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let client = Client {
+        chunks: chunks.iter().map(|chunk| chunk.bytes().collect()).collect(),
+        output: output.clone(),
+        nonblocking: Cell::new(false),
+    };
+
+    let mut stub = GdbStub::with_connection(client);
+    let mut session = Session::Attached;
+    for _ in 0..10 {
+        session = stub.run_frame(processor, [false; 16]).unwrap();
+        if session != Session::Attached {
+            break;
+        }
+    }
+    let output = String::from_utf8(output.borrow().clone()).unwrap();
+    (output, session)
+}
+
+// The replies after the one to QStartNoAckMode
+fn replies(processor: &mut Processor, packets: &[&str]) -> String {
+    let script = no_ack() + &packets.iter().map(|data| packet(data)).collect::<String>();
+    let (output, _) = session(processor, &[&script]);
+    output.strip_prefix(&format!("+{}", packet("OK"))).unwrap().to_string()
+}
+
+#[test]
+fn acknowledges_packets_and_rejects_bad_checksums() {
+    let mut processor = processor(Quirks::modern(), &[]);
+    let (output, session) = session(&mut processor, &["+$?#00", "$?#3f", "+"]);
+    assert_eq!(output, format!("-+{}", packet("S05")));
+    assert_eq!(session, Session::Detached);
+}
+
+#[test]
+fn escaped_bytes_count_towards_the_checksum() {
+    let mut processor = processor(Quirks::modern(), &[]);
+    // }] is an escaped }, which isn't a command the stub knows
+    let (output, _) = session(&mut processor, &["$}]#da", "+"]);
+    assert_eq!(output, format!("+{}", packet("")));
+}
+
+#[test]
+fn reads_and_writes_registers() {
+    let mut processor = processor(Quirks::modern(), &[]);
+    processor.set_register(0, 0x12);
+    processor.set_register(0xF, 0xAB);
+    processor.set_index(0x0345);
+
+    let registers = format!("12{}ab{}{}{}", "00".repeat(14), "4503", "0002", "0000");
+    assert_eq!(replies(&mut processor, &["g", "p11", "P1=7f", "p1", "p13"]),
+        [packet(&registers), packet("0002"), packet("OK"), packet("7f"), packet("E01")].concat());
+    assert_eq!(processor.registers()[1], 0x7F);
+
+    let written = format!("{}{}{}{}", "01".repeat(16), "3412", "0403", "0000");
+    assert_eq!(replies(&mut processor, &[&format!("G{}", written)]), packet("OK"));
+    assert_eq!((processor.registers()[0xF], processor.index(), processor.pc()), (1, 0x1234, 0x304));
+}
+
+#[test]
+fn stack_pointer_keeps_its_width_on_a_deep_stack() {
+    let mut quirks = Quirks::modern();
+    quirks.stack_depth = None;
+    let mut processor = processor(quirks, &[]);
+    processor.set_stack_pointer(300);
+
+    assert_eq!(replies(&mut processor, &["p12"]), packet("2c01"));
+    let output = replies(&mut processor, &["g"]);
+    // 16 8 bit registers and 3 16 bit ones
+    assert_eq!(output.len(), packet(&"0".repeat(16 * 2 + 3 * 4)).len());
+    assert!(output.contains("00022c01#"), "{}", output);
+}
+
+#[test]
+fn reads_and_writes_memory() {
+    let mut processor = processor(Quirks::modern(), &[0x6001, 0x00E0]);
+    assert_eq!(replies(&mut processor, &["m200,4", "M300,2:abcd", "m300,2", "M300,2:ab"]),
+        [packet("600100e0"), packet("OK"), packet("abcd"), packet("E01")].concat());
+
+    processor.set_memory_policy(MemoryPolicy::Fault);
+    assert_eq!(replies(&mut processor, &["mffe,4", "m1000,1"]), [packet("0000"), packet("E01")].concat());
+}
+
+#[test]
+fn continues_to_a_breakpoint() {
+    // v0 := 1, v1 := 2, loop
+    let mut processor = processor(Quirks::modern(), &[0x6001, 0x6102, 0x1204]);
+    assert_eq!(replies(&mut processor, &["Z0,202", "c", "z0,202", "Z2,300"]),
+        [packet("OK"), packet("S05"), packet("OK"), packet("")].concat());
+    assert_eq!(processor.pc(), 0x202);
+    assert_eq!(processor.registers()[..2], [1, 0]);
+}
+
+#[test]
+fn steps_one_instruction() {
+    let mut processor = processor(Quirks::modern(), &[0x6001, 0x6102, 0x1204]);
+    assert_eq!(replies(&mut processor, &["s", "s"]), [packet("S05"), packet("S05")].concat());
+    assert_eq!(processor.pc(), 0x204);
+}
+
+#[test]
+fn faults_stop_with_a_signal() {
+    // return with nothing on the stack
+    let mut processor = processor(Quirks::modern(), &[0x00EE]);
+    assert_eq!(replies(&mut processor, &["c"]), packet("S06"));
+
+    let mut processor = self::processor(Quirks::modern(), &[0xFFFF]);
+    assert_eq!(replies(&mut processor, &["c"]), packet("S04"));
+
+    // i := 0xFFF, load v1 reads past the end
+    let mut processor = self::processor(Quirks::modern(), &[0xAFFF, 0xF165]);
+    processor.set_memory_policy(MemoryPolicy::Fault);
+    assert_eq!(replies(&mut processor, &["c"]), packet("S0b"));
+}
+
+#[test]
+fn interrupt_keeps_the_bytes_that_arrive_with_it() {
+    // loop forever
+    let mut processor = processor(Quirks::modern(), &[0x1200]);
+    let continue_packet = no_ack() + &packet("c");
+    // a packet while running, then ^C on a later frame
+    let (output, session) = session(&mut processor, &[&continue_packet, &packet("?"), "\x03"]);
+    assert_eq!(output, format!("+{}{}{}", packet("OK"), packet("S02"), packet("S05")));
+    assert_eq!(session, Session::Detached);
+}
+
+#[test]
+fn exit_ends_the_session() {
+    let mut processor = processor(Quirks::modern(), &[0x00FD]);
+    let continue_packet = no_ack() + &packet("c");
+    let (output, session) = session(&mut processor, &[&continue_packet]);
+    assert!(output.ends_with(&packet("W00")), "{}", output);
+    assert_eq!(session, Session::Ended);
+}
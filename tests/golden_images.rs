@@ -0,0 +1,62 @@
+// Runs the test ROMs bundled in src/roms for a fixed number of frames and compares the final screen
+// with the image checked in under tests/golden. After an intended change to what a ROM draws,
+// regenerate the images with UPDATE_GOLDEN=1 cargo test --test golden_images and review the diff.
+use chip8_emulator_rust::{CHIP8_START_OF_PROGRAM, drivers::CartridgeDriver, error::ErrorPolicy, quirks::Platform, processor::Processor};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const FRAMES: usize = 300;
+
+fn run(rom: &str, platform: Platform) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/roms").join(rom);
+    let cartridge = CartridgeDriver::open(path.to_str().unwrap()).unwrap();
+
+    let mut processor = Processor::new(platform.quirks());
+    processor.set_error_policy(ErrorPolicy::Halt);
+    processor.seed_random(0);
+    processor.load(cartridge.rom, cartridge.size, CHIP8_START_OF_PROGRAM);
+
+    for _ in 0..FRAMES {
+        if let Err(error) = processor.run_frame([false; 16]) {
+            panic!("{} faulted: {}", rom, error);
+        }
+    }
+
+    processor.framebuffer().to_string()
+}
+
+fn check(rom: &str, platform: Platform, golden: &str) {
+    let actual = run(rom, platform);
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(golden);
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|error| panic!("can't read {}: {}", path.display(), error));
+    assert!(actual == expected, "{} on {} doesn't match {}\nexpected:\n{}\nactual:\n{}", rom, platform, golden, expected, actual);
+}
+
+#[test]
+fn test_opcode() {
+    check("test_opcode.ch8", Platform::Modern, "test_opcode.txt");
+}
+
+// The last check of SCTEST expects Fx1E to set VF when I passes 0xFFF, which only the Amiga
+// interpreter did, so the golden image shows its "ERROR 24" screen.
+#[test]
+fn sctest() {
+    check("SCTEST.CH8", Platform::SuperChip, "sctest.txt");
+}
+
+#[test]
+fn c8_test() {
+    check("c8_test.c8", Platform::Modern, "c8_test.txt");
+}
+
+#[test]
+fn chip8_test_rom() {
+    check("chip8-test-rom.ch8", Platform::Modern, "chip8_test_rom.txt");
+}
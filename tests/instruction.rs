@@ -0,0 +1,54 @@
+// Decoding and encoding opcodes, and running F000 nnnn from the instruction alone.
+use chip8_emulator_rust::{CHIP8_START_OF_PROGRAM, XOCHIP_PROGRAM_SIZE, assembler::assemble, disassembler::Disassembly, instruction::Instruction, processor::Processor, quirks::{InstructionSet, Quirks}};
+
+#[test]
+fn every_opcode_encodes_back_to_itself() {
+    for opcode in 0..=0xFFFF {
+        let instruction = Instruction::decode(opcode, 0x1234);
+        assert_eq!(instruction.opcode(), opcode, "{:?}", instruction);
+        assert_eq!(Instruction::decode(instruction.opcode(), instruction.second_word().unwrap_or(0)), instruction);
+    }
+}
+
+#[test]
+fn long_load_takes_the_next_word() {
+    let instruction = Instruction::decode(0xF000, 0xBEEF);
+    assert_eq!(instruction, Instruction::LoadIndexLong(0xBEEF));
+    assert_eq!(instruction.second_word(), Some(0xBEEF));
+    assert_eq!(instruction.size(), 4);
+    assert_eq!(instruction.to_string(), "LD I, 0xBEEF");
+    assert_eq!(Instruction::decode(0xA123, 0xBEEF).second_word(), None);
+}
+
+#[test]
+fn extensions_decode_per_instruction_set() {
+    assert_eq!(Instruction::decode_for(0xF000, 0x1234, InstructionSet::SuperChip), Instruction::Unknown(0xF000));
+    assert_eq!(Instruction::decode_for(0x00FF, 0, InstructionSet::Chip8), Instruction::Sys(0x0FF));
+    assert_eq!(Instruction::decode_for(0x00FF, 0, InstructionSet::SuperChip), Instruction::HighRes);
+}
+
+#[test]
+fn execute_long_load_without_memory() {
+    let mut processor = Processor::new(Quirks::xochip());
+    processor.load([0u8; XOCHIP_PROGRAM_SIZE], 0, CHIP8_START_OF_PROGRAM);
+    processor.execute(Instruction::LoadIndexLong(0xBEEF)).unwrap();
+    assert_eq!(processor.index(), 0xBEEF);
+    assert_eq!(processor.pc(), 0x204);
+}
+
+#[test]
+fn long_load_through_the_tools() {
+    let rom = assemble(": main i := long data : data 0x11").unwrap();
+    assert_eq!(rom, [0xF0, 0x00, 0x02, 0x04, 0x11]);
+
+    let mut program = [0u8; XOCHIP_PROGRAM_SIZE];
+    program[..rom.len()].copy_from_slice(&rom);
+    let mut processor = Processor::new(Quirks::xochip());
+    processor.load(program, rom.len(), CHIP8_START_OF_PROGRAM);
+    assert_eq!(processor.read_instruction(0x200), Ok(Instruction::LoadIndexLong(0x204)));
+    processor.cycle([false; 16]).unwrap();
+    assert_eq!(processor.index(), 0x204);
+
+    let disassembly = Disassembly::new(&rom, 0x200, InstructionSet::XoChip).to_string();
+    assert!(disassembly.contains("F000 0204  LD I, data_204"), "{}", disassembly);
+}
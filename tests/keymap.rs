@@ -0,0 +1,118 @@
+// Keymap presets and the TOML subset keymap files are written in.
+use chip8_emulator_rust::keymap::{Keymap, PRESETS};
+
+fn parse(source: &str) -> Keymap {
+    Keymap::parse(source, None).unwrap()
+}
+
+fn error(source: &str) -> (usize, String) {
+    let error = Keymap::parse(source, None).unwrap_err();
+    (error.line, error.message)
+}
+
+#[test]
+fn presets() {
+    for name in PRESETS {
+        let keymap = Keymap::preset(name).unwrap();
+        assert_eq!(keymap.iter_keys().count(), 16, "{}", name);
+        // no host key presses two CHIP-8 keys
+        for (key, host_key) in keymap.iter_keys() {
+            assert_eq!(keymap.key_for(host_key), Some(key), "{}", name);
+        }
+    }
+
+    assert_eq!(Keymap::preset("Dvorak"), Keymap::preset("dvorak"));
+    assert_eq!(Keymap::preset("colemak"), None);
+    assert_eq!(Keymap::default(), Keymap::preset("qwerty").unwrap());
+    assert_eq!(Keymap::default().host_keys(5), ["W"]);
+    assert_eq!(Keymap::preset("azerty").unwrap().host_keys(5), ["Z"]);
+    assert_eq!(Keymap::default().buttons(2), ["dpup"]);
+}
+
+#[test]
+fn dvorak_takes_p() {
+    assert_eq!(Keymap::preset("dvorak").unwrap().key_for("p"), Some(0xD));
+    assert_eq!(Keymap::default().key_for("P"), None);
+}
+
+#[test]
+fn preset_in_a_file() {
+    let keymap = parse("preset = \"numpad\"\n");
+    assert_eq!(keymap, Keymap::preset("numpad").unwrap());
+
+    // a preset replaces the keys before it, the gamepad is kept
+    let keymap = parse("5 = \"Up\"\npreset = \"azerty\"\n[gamepad]\n5 = \"b\"\n");
+    assert_eq!(keymap.host_keys(5), ["Z"]);
+    assert_eq!(keymap.buttons(5), ["b"]);
+
+    assert_eq!(error("preset = \"colemak\""), (1, String::from("unknown preset 'colemak', expected one of: qwerty, azerty, dvorak, numpad")));
+    assert_eq!(error("preset = [\"qwerty\"]"), (1, String::from("preset should be a string")));
+}
+
+#[test]
+fn comments_and_blank_lines() {
+    let keymap = parse("# a keymap\n\n   # indented\n5 = \"Up\" # trailing\n[gamepad] # buttons\n5 = [\"a\"] # first\n");
+    assert_eq!(keymap.host_keys(5), ["Up"]);
+    assert_eq!(keymap.buttons(5), ["a"]);
+    // a comment inside an unclosed list ends the line
+    assert_eq!(error("5 = [\"a\", # first\n").0, 1);
+    assert_eq!(parse("5 = \"#\"").host_keys(5), ["#"]);
+}
+
+#[test]
+fn quoting() {
+    let keymap = parse("\"5\" = \"Keypad 5\"\n0x6 = \"Right\"\n7 = \"\\\"\"\n8 = \"\\\\\"\n9 = []\n");
+    assert_eq!(keymap.host_keys(5), ["Keypad 5"]);
+    assert_eq!(keymap.host_keys(6), ["Right"]);
+    assert_eq!(keymap.host_keys(7), ["\""]);
+    assert_eq!(keymap.host_keys(8), ["\\"]);
+    assert!(keymap.host_keys(9).is_empty());
+
+    assert_eq!(parse("[\"roms\".\"My Game\"]\n5 = \"Up\"\n"), Keymap::default());
+    assert_eq!(Keymap::parse("[\"roms\".\"My Game\"]\n5 = \"Up\"\n", Some("My Game.ch8")).unwrap().host_keys(5), ["Up"]);
+
+    assert_eq!(error("5 = Up"), (1, String::from("expected a quoted string")));
+    assert_eq!(error("5 = \"Up"), (1, String::from("unterminated string")));
+    assert_eq!(error("5 = \"\\n\""), (1, String::from("only \\\" and \\\\ escapes are supported")));
+    assert_eq!(error("5 = \"Up\" \"Down\""), (1, String::from("unexpected '\"Down\"'")));
+    assert_eq!(error("5 = [\"Up\" \"Down\"]"), (1, String::from("expected ']'")));
+    assert_eq!(error("[roms"), (1, String::from("expected ']'")));
+    assert_eq!(error("5 \"Up\""), (1, String::from("expected '='")));
+}
+
+#[test]
+fn bad_keys() {
+    assert_eq!(error("\n\nG = \"Up\""), (3, String::from("'G' is not a CHIP-8 key, expected 0-F or preset")));
+    assert_eq!(error("10 = \"Up\""), (1, String::from("'10' is not a CHIP-8 key, expected 0-F or preset")));
+    assert_eq!(error("[gamepad]\npreset = \"a\""), (2, String::from("'preset' is not a CHIP-8 key, expected 0-F")));
+    assert_eq!(error("= \"Up\""), (1, String::from("expected a key")));
+    assert_eq!(error("[keys]\n5 = \"Up\""), (2, String::from("unknown table [keys]")));
+    assert_eq!(parse("a = \"Up\"\nF = \"Down\"").host_keys(0xA), ["Up"]);
+}
+
+#[test]
+fn duplicate_bindings() {
+    // the later line wins, and a host key moves rather than pressing two CHIP-8 keys
+    let keymap = parse("5 = \"Up\"\n5 = \"Down\"\n6 = [\"w\", \"Left\"]\n");
+    assert_eq!(keymap.host_keys(5), ["Down"]);
+    assert_eq!(keymap.host_keys(6), ["w", "Left"]);
+    assert_eq!(keymap.key_for("W"), Some(6));
+    assert_eq!(keymap.iter_keys().filter(|&(_, name)| name.eq_ignore_ascii_case("w")).count(), 1);
+
+    let keymap = parse("[gamepad]\n2 = \"a\"\n5 = \"a\"\n");
+    assert_eq!(keymap.buttons(5), ["a"]);
+    assert!(keymap.buttons(2).is_empty());
+}
+
+#[test]
+fn rom_tables() {
+    let source = "[roms.TANK]\n2 = \"Up\"\n[roms.TANK.gamepad]\n5 = \"b\"\n[gamepad]\n5 = \"a\"\n";
+    let keymap = Keymap::parse(source, Some("tank.ch8")).unwrap();
+    assert_eq!(keymap.host_keys(2), ["Up"]);
+    // the ROM's table wins though the main one comes after it
+    assert_eq!(keymap.buttons(5), ["b"]);
+
+    let keymap = Keymap::parse(source, Some("BRIX.ch8")).unwrap();
+    assert_eq!(keymap.host_keys(2), ["2"]);
+    assert_eq!(keymap.buttons(5), ["a"]);
+}
@@ -0,0 +1,127 @@
+// Fx0A: with key_wait_on_release a key has to go down and come back up while the wait is on,
+// without it a held key finishes the wait straight away.
+use chip8_emulator_rust::{CHIP8_START_OF_PROGRAM, XOCHIP_PROGRAM_SIZE, keypad::KeyEvent, processor::Processor, quirks::Quirks};
+
+fn processor(quirks: Quirks, opcodes: &[u16]) -> Processor {
+    let mut program = [0u8; XOCHIP_PROGRAM_SIZE];
+    for (i, opcode) in opcodes.iter().enumerate() {
+        program[i * 2..i * 2 + 2].copy_from_slice(&opcode.to_be_bytes());
+    }
+
+    let mut processor = Processor::new(quirks);
+    processor.load(program, opcodes.len() * 2, CHIP8_START_OF_PROGRAM);
+    processor
+}
+
+// v3 := key, v1 := 1
+fn waiting(quirks: Quirks) -> Processor {
+    let mut processor = processor(quirks, &[0xF30A, 0x6101]);
+    processor.cycle([false; 16]).unwrap();
+    assert!(processor.waiting_for_key());
+    processor
+}
+
+fn event(key: u8, down: bool) -> KeyEvent {
+    KeyEvent { key, down, timestamp: 0 }
+}
+
+fn keys(down: &[usize]) -> [bool; 16] {
+    let mut keypad = [false; 16];
+    for &key in down {
+        keypad[key] = true;
+    }
+    keypad
+}
+
+#[test]
+fn waits_for_the_release() {
+    let mut processor = waiting(Quirks::modern());
+    processor.key_event(event(7, true));
+    processor.cycle(keys(&[7])).unwrap();
+    assert!(processor.waiting_for_key());
+
+    processor.key_event(event(7, false));
+    assert!(!processor.waiting_for_key());
+    assert_eq!(processor.registers()[3], 7);
+
+    // the wait is over, the next instruction runs
+    processor.cycle([false; 16]).unwrap();
+    assert_eq!(processor.registers()[1], 1);
+}
+
+#[test]
+fn press_and_release_between_frames_counts() {
+    let mut processor = waiting(Quirks::modern());
+    processor.key_event(event(0xA, true));
+    processor.key_event(event(0xA, false));
+    processor.cycle([false; 16]).unwrap();
+    assert_eq!(processor.registers()[3], 0xA);
+    assert_eq!(processor.registers()[1], 1);
+}
+
+#[test]
+fn key_held_before_the_wait_doesnt_count() {
+    let mut processor = processor(Quirks::modern(), &[0xF30A, 0x6101]);
+    processor.cycle(keys(&[5])).unwrap();
+    assert!(processor.waiting_for_key());
+
+    processor.cycle([false; 16]).unwrap();
+    assert!(processor.waiting_for_key());
+
+    processor.cycle(keys(&[6])).unwrap();
+    processor.cycle([false; 16]).unwrap();
+    assert!(!processor.waiting_for_key());
+    assert_eq!(processor.registers()[3], 6);
+}
+
+#[test]
+fn first_key_released_wins() {
+    let mut processor = waiting(Quirks::modern());
+    processor.key_event(event(1, true));
+    processor.key_event(event(2, true));
+    processor.key_event(event(2, false));
+    processor.key_event(event(1, false));
+    assert_eq!(processor.registers()[3], 2);
+}
+
+#[test]
+fn keypad_changes_without_events() {
+    let mut processor = waiting(Quirks::modern());
+    processor.cycle(keys(&[0xF])).unwrap();
+    assert!(processor.waiting_for_key());
+    processor.cycle([false; 16]).unwrap();
+    assert!(!processor.waiting_for_key());
+    assert_eq!(processor.registers()[3], 0xF);
+}
+
+#[test]
+fn without_the_quirk_a_held_key_finishes_the_wait() {
+    let quirks = Quirks { key_wait_on_release: false, ..Quirks::modern() };
+    let mut processor = processor(quirks, &[0xF30A, 0x6101]);
+    processor.cycle(keys(&[5])).unwrap();
+    assert!(processor.waiting_for_key());
+
+    // held since before Fx0A and not released
+    processor.cycle(keys(&[5])).unwrap();
+    assert!(!processor.waiting_for_key());
+    assert_eq!(processor.registers()[3], 5);
+
+    // a release alone doesn't
+    let mut processor = waiting(quirks);
+    processor.key_event(event(4, true));
+    processor.key_event(event(4, false));
+    assert!(processor.waiting_for_key());
+}
+
+#[test]
+fn a_pending_press_survives_a_snapshot() {
+    let mut processor = waiting(Quirks::modern());
+    processor.key_event(event(9, true));
+    let snapshot = processor.snapshot();
+
+    let mut restored = waiting(Quirks::modern());
+    restored.restore(&snapshot);
+    restored.key_event(event(9, false));
+    assert!(!restored.waiting_for_key());
+    assert_eq!(restored.registers()[3], 9);
+}
@@ -0,0 +1,100 @@
+// What each MemoryPolicy does with an address past the end of RAM, on Memory itself and through
+// an I relative instruction on the processor.
+use chip8_emulator_rust::{CHIP8_RAM_SIZE_BYTES, CHIP8_START_OF_PROGRAM, XOCHIP_PROGRAM_SIZE, error::{ErrorPolicy, ExecErrorKind}, memory::{Access, AccessKind, Memory, MemoryPolicy, OutOfBounds}, processor::Processor, quirks::Quirks};
+
+fn memory(policy: MemoryPolicy) -> Memory {
+    let mut memory = Memory::new(16);
+    memory.set_policy(policy);
+    for (i, byte) in memory.as_mut_slice().iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    memory
+}
+
+#[test]
+fn addresses_in_range_are_the_same_under_every_policy() {
+    for policy in [MemoryPolicy::Wrap, MemoryPolicy::Fault, MemoryPolicy::Clamp] {
+        let memory = memory(policy);
+        assert_eq!(memory.resolve(15), Ok(15));
+        assert_eq!(memory.peek_word(4), Ok(0x0405));
+    }
+}
+
+#[test]
+fn wrap() {
+    let mut memory = memory(MemoryPolicy::Wrap);
+    assert_eq!(memory.peek(17), Ok(1));
+    assert_eq!(memory.peek_word(15), Ok(0x0F00));
+    memory.write(18, 0xAA).unwrap();
+    assert_eq!(memory.as_slice()[2], 0xAA);
+}
+
+#[test]
+fn fault() {
+    let mut memory = memory(MemoryPolicy::Fault);
+    assert_eq!(memory.peek(16), Err(OutOfBounds { address: 16 }));
+    assert_eq!(memory.peek_word(15), Err(OutOfBounds { address: 16 }));
+    assert!(memory.write(20, 0xAA).is_err());
+    assert!(memory.as_slice().iter().enumerate().all(|(i, &byte)| byte == i as u8));
+}
+
+#[test]
+fn clamp() {
+    let mut memory = memory(MemoryPolicy::Clamp);
+    assert_eq!(memory.peek(1000), Ok(15));
+    memory.write(16, 0xAA).unwrap();
+    assert_eq!(memory.as_slice()[15], 0xAA);
+}
+
+#[test]
+fn tracking_records_the_resolved_address() {
+    let mut memory = memory(MemoryPolicy::Wrap);
+    memory.read(3).unwrap();
+    assert!(memory.take_accesses().is_empty());
+
+    memory.set_tracking(true);
+    memory.read(3).unwrap();
+    memory.write(17, 0).unwrap();
+    memory.peek(4).unwrap();
+    assert_eq!(memory.take_accesses(), [
+        Access { address: 3, kind: AccessKind::Read },
+        Access { address: 1, kind: AccessKind::Write },
+    ]);
+}
+
+// i := 0xFFF, v0 := 0xAB, v1 := 0xCD, save v1
+fn store_past_the_end(policy: MemoryPolicy) -> (Processor, Result<(), ExecErrorKind>) {
+    let opcodes = [0xAFFFu16, 0x60AB, 0x61CD, 0xF155];
+    let mut program = [0u8; XOCHIP_PROGRAM_SIZE];
+    for (i, opcode) in opcodes.iter().enumerate() {
+        program[i * 2..i * 2 + 2].copy_from_slice(&opcode.to_be_bytes());
+    }
+
+    let mut processor = Processor::new(Quirks::modern());
+    processor.set_error_policy(ErrorPolicy::Halt);
+    processor.set_memory_policy(policy);
+    processor.load(program, opcodes.len() * 2, CHIP8_START_OF_PROGRAM);
+    let mut result = Ok(());
+    for _ in opcodes {
+        result = processor.cycle([false; 16]).map_err(|error| error.kind);
+    }
+    (processor, result)
+}
+
+#[test]
+fn policies_apply_to_i_relative_instructions() {
+    let last = CHIP8_RAM_SIZE_BYTES - 1;
+
+    let (processor, result) = store_past_the_end(MemoryPolicy::Wrap);
+    assert_eq!(result, Ok(()));
+    assert_eq!(processor.memory().as_slice()[last], 0xAB);
+    assert_eq!(processor.memory().as_slice()[0], 0xCD);
+
+    let (processor, result) = store_past_the_end(MemoryPolicy::Clamp);
+    assert_eq!(result, Ok(()));
+    assert_eq!(processor.memory().as_slice()[last], 0xCD);
+
+    let (processor, result) = store_past_the_end(MemoryPolicy::Fault);
+    assert_eq!(result, Err(ExecErrorKind::MemoryOutOfBounds(CHIP8_RAM_SIZE_BYTES)));
+    assert_eq!(processor.halted().map(|error| error.pc), Some(0x206));
+}
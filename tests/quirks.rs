@@ -0,0 +1,72 @@
+// Each preset runs the ambiguous opcodes the way its platform did. The programs are written as
+// opcodes, see Instruction for what each one is.
+use chip8_emulator_rust::{CHIP8_START_OF_PROGRAM, XOCHIP_PROGRAM_SIZE, processor::Processor, quirks::{LoadStoreIndex, Platform, Quirks}};
+
+fn run(quirks: Quirks, opcodes: &[u16]) -> Processor {
+    let mut program = [0u8; XOCHIP_PROGRAM_SIZE];
+    for (i, opcode) in opcodes.iter().enumerate() {
+        program[i * 2..i * 2 + 2].copy_from_slice(&opcode.to_be_bytes());
+    }
+
+    let mut processor = Processor::new(quirks);
+    processor.load(program, opcodes.len() * 2, CHIP8_START_OF_PROGRAM);
+    for _ in opcodes {
+        processor.cycle([false; 16]).unwrap();
+    }
+    processor
+}
+
+#[test]
+fn shift_uses_vy() {
+    // v1 := 1, v2 := 4, v1 >>= v2
+    let program = [0x6101, 0x6204, 0x8126];
+    assert_eq!(run(Quirks::cosmac_vip(), &program).registers()[1], 2);
+    assert_eq!(run(Quirks::modern(), &program).registers()[1], 0);
+}
+
+#[test]
+fn load_store_index() {
+    // i := 0x300, save v2
+    let program = [0xA300, 0xF255];
+    assert_eq!(run(Quirks::cosmac_vip(), &program).index(), 0x303);
+    assert_eq!(run(Quirks::chip48(), &program).index(), 0x302);
+    assert_eq!(run(Quirks::superchip(), &program).index(), 0x300);
+}
+
+#[test]
+fn logic_resets_vf() {
+    // vf := 7, v1 |= v2
+    let program = [0x6F07, 0x8121];
+    assert_eq!(run(Quirks::cosmac_vip(), &program).registers()[0xF], 0);
+    assert_eq!(run(Quirks::modern(), &program).registers()[0xF], 7);
+}
+
+#[test]
+fn jump_uses_vx() {
+    // v0 := 2, v3 := 4, jump0 0x300
+    let program = [0x6002, 0x6304, 0xB300];
+    assert_eq!(run(Quirks::chip48(), &program).pc(), 0x304);
+    assert_eq!(run(Quirks::modern(), &program).pc(), 0x302);
+}
+
+#[test]
+fn flag_wins_when_vf_is_the_destination() {
+    // vf := 0xFF, v1 := 1, vf += v1 carries
+    let quirks = Quirks::modern();
+    assert_eq!(run(quirks, &[0x6FFF, 0x6101, 0x8F14]).registers()[0xF], 1);
+    // vf := 1, v1 := 2, vf -= v1 borrows
+    assert_eq!(run(quirks, &[0x6F01, 0x6102, 0x8F15]).registers()[0xF], 0);
+    // vf := 0x81, vf <<= vf shifts a 1 out
+    assert_eq!(run(quirks, &[0x6F81, 0x8FFE]).registers()[0xF], 1);
+}
+
+#[test]
+fn presets_by_name() {
+    for platform in Platform::ALL {
+        assert_eq!(platform.name().parse::<Platform>(), Ok(platform));
+    }
+    assert_eq!("COSMAC-VIP".parse::<Platform>().unwrap().quirks(), Quirks::cosmac_vip());
+    assert_eq!("xo-chip".parse::<Platform>().unwrap().quirks().load_store, LoadStoreIndex::IncrementByXPlusOne);
+    assert!("chip-9".parse::<Platform>().unwrap_err().contains("vip, chip48, schip, xochip, modern"));
+    assert_eq!(Quirks::default(), Quirks::modern());
+}
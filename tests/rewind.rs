@@ -0,0 +1,111 @@
+// The rewind ring buffer: what it keeps, what it drops first and how many bytes it counts.
+use chip8_emulator_rust::{CHIP8_RAM_SIZE_BYTES, processor::Processor, quirks::Quirks, rewind::{Rewind, DEFAULT_MAX_BYTES}};
+
+// Record frames, marking each snapshot with its frame number in V0/V1
+fn record(rewind: &mut Rewind, processor: &mut Processor, frames: std::ops::Range<u16>) {
+    for frame in frames {
+        processor.set_register(0, frame as u8);
+        processor.set_register(1, (frame >> 8) as u8);
+        rewind.record(processor);
+    }
+}
+
+fn frame(processor: &Processor) -> u16 {
+    let registers = processor.registers();
+    (registers[1] as u16) << 8 | registers[0] as u16
+}
+
+#[test]
+fn steps_back_newest_first_and_drops_the_oldest() {
+    let mut processor = Processor::new(Quirks::modern());
+    // one second of one snapshot a frame
+    let mut rewind = Rewind::new(1, DEFAULT_MAX_BYTES, 1);
+    record(&mut rewind, &mut processor, 0..70);
+    assert_eq!(rewind.len(), 60);
+    assert_eq!(rewind.seconds(), 1.0);
+
+    let mut frames = Vec::new();
+    while rewind.step_back(&mut processor) {
+        frames.push(frame(&processor));
+    }
+    assert_eq!(frames, (10..70).rev().collect::<Vec<u16>>());
+    assert!(!rewind.step_back(&mut processor));
+}
+
+#[test]
+fn snapshots_every_interval() {
+    let mut processor = Processor::new(Quirks::modern());
+    let mut rewind = Rewind::new(10, DEFAULT_MAX_BYTES, 3);
+    record(&mut rewind, &mut processor, 0..10);
+    assert_eq!(rewind.len(), 3);
+    rewind.step_back(&mut processor);
+    assert_eq!(frame(&processor), 8);
+}
+
+#[test]
+fn counts_the_bytes_it_keeps() {
+    let mut processor = Processor::new(Quirks::modern());
+    let size = processor.snapshot().size();
+    let mut rewind = Rewind::new(60, DEFAULT_MAX_BYTES, 1);
+
+    record(&mut rewind, &mut processor, 0..5);
+    assert_eq!(rewind.size(), 5 * size);
+    rewind.step_back(&mut processor);
+    assert_eq!(rewind.size(), 4 * size);
+    rewind.clear();
+    assert_eq!((rewind.size(), rewind.len()), (0, 0));
+}
+
+#[test]
+fn byte_budget_caps_the_seconds() {
+    let mut processor = Processor::new(Quirks::modern());
+    let size = processor.snapshot().size();
+    let mut rewind = Rewind::new(60, 3 * size, 1);
+    record(&mut rewind, &mut processor, 0..10);
+    assert_eq!(rewind.len(), 3);
+    assert_eq!(rewind.size(), 3 * size);
+}
+
+#[test]
+fn bigger_snapshots_push_out_more_small_ones() {
+    let mut processor = Processor::new(Quirks::modern());
+    let small = processor.snapshot().size();
+    processor.set_quirks(Quirks::xochip());
+    let big = processor.snapshot().size();
+    processor.set_quirks(Quirks::modern());
+
+    let mut rewind = Rewind::new(60, big + 2 * small, 1);
+    record(&mut rewind, &mut processor, 0..5);
+    assert_eq!(rewind.size(), 5 * small);
+
+    // 64K of RAM from here on, only two of the small snapshots still fit beside it
+    processor.set_quirks(Quirks::xochip());
+    record(&mut rewind, &mut processor, 5..6);
+    assert_eq!(rewind.len(), 3);
+    assert_eq!(rewind.size(), big + 2 * small);
+
+    // going back past the switch puts the 4K machine back
+    rewind.step_back(&mut processor);
+    assert_eq!(rewind.size(), 2 * small);
+    rewind.step_back(&mut processor);
+    assert_eq!(frame(&processor), 4);
+    assert_eq!(processor.memory().size(), CHIP8_RAM_SIZE_BYTES);
+    assert_eq!(rewind.size(), small);
+}
+
+#[test]
+fn zero_seconds_turns_it_off() {
+    let mut processor = Processor::new(Quirks::modern());
+    let mut rewind = Rewind::new(0, DEFAULT_MAX_BYTES, 1);
+    record(&mut rewind, &mut processor, 0..10);
+    assert!(rewind.is_empty());
+}
+
+#[test]
+fn default_keeps_a_minute_of_xochip() {
+    let mut processor = Processor::new(Quirks::xochip());
+    let mut rewind = Rewind::default();
+    record(&mut rewind, &mut processor, 0..4000);
+    assert_eq!(rewind.seconds(), 60.0);
+    assert!(rewind.size() <= DEFAULT_MAX_BYTES);
+}
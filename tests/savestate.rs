@@ -0,0 +1,126 @@
+// Save state files: what goes in comes back out, and files that can't be right are refused.
+use chip8_emulator_rust::{CHIP8_RAM_SIZE_BYTES, CHIP8_START_OF_PROGRAM, XOCHIP_PROGRAM_SIZE, processor::Processor, quirks::Quirks, savestate::{self, SaveStateError, MAGIC, VERSION}};
+
+const ROM_HASH: u64 = 0x0123_4567_89AB_CDEF;
+// magic, version and ROM hash come before the memory size
+const MEMORY_SIZE: usize = 4 + 2 + 8;
+const SCREEN_SIZE: usize = MEMORY_SIZE + 4 + CHIP8_RAM_SIZE_BYTES;
+
+fn processor(quirks: Quirks, opcodes: &[u16]) -> Processor {
+    let mut program = [0u8; XOCHIP_PROGRAM_SIZE];
+    for (i, opcode) in opcodes.iter().enumerate() {
+        program[i * 2..i * 2 + 2].copy_from_slice(&opcode.to_be_bytes());
+    }
+
+    let mut processor = Processor::new(quirks);
+    processor.load(program, opcodes.len() * 2, CHIP8_START_OF_PROGRAM);
+    processor
+}
+
+// A machine partway through a ROM: a sprite on screen, a call on the stack, timers running
+fn running(quirks: Quirks) -> Processor {
+    // call 204 past a jump that never runs, v3 := 0x2A, delay := v3, i := font 3, draw v3 v3 5, jump 20C
+    let mut processor = processor(quirks, &[0x2204, 0x1202, 0x632A, 0xF315, 0xF329, 0xD335, 0x120C]);
+    for _ in 0..6 {
+        processor.cycle([false; 16]).unwrap();
+    }
+    processor
+}
+
+fn encoded() -> Vec<u8> {
+    savestate::encode(&running(Quirks::modern()).snapshot(), ROM_HASH)
+}
+
+#[test]
+fn round_trip() {
+    for quirks in [Quirks::modern(), Quirks::superchip(), Quirks::xochip()] {
+        let snapshot = running(quirks).snapshot();
+        let decoded = savestate::decode(&savestate::encode(&snapshot, ROM_HASH), ROM_HASH).unwrap();
+        assert_eq!(decoded, snapshot);
+
+        let mut restored = processor(Quirks::modern(), &[]);
+        restored.restore(&decoded);
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(restored.registers()[3], 0x2A);
+        assert_eq!(restored.framebuffer(), running(quirks).framebuffer());
+    }
+}
+
+#[test]
+fn round_trip_through_a_file() {
+    let path = std::env::temp_dir().join(format!("chip8-savestate-{}.c8s", std::process::id()));
+    let snapshot = running(Quirks::xochip()).snapshot();
+    savestate::save(&path, &snapshot, ROM_HASH).unwrap();
+    let loaded = savestate::load(&path, ROM_HASH);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap(), snapshot);
+}
+
+#[test]
+fn bad_magic() {
+    let mut bytes = encoded();
+    bytes[..4].copy_from_slice(b"C8SX");
+    assert!(matches!(savestate::decode(&bytes, ROM_HASH), Err(SaveStateError::NotASaveState)));
+    assert!(matches!(savestate::decode(b"C8", ROM_HASH), Err(SaveStateError::NotASaveState)));
+    assert!(matches!(savestate::decode(&[], ROM_HASH), Err(SaveStateError::NotASaveState)));
+}
+
+#[test]
+fn other_versions_are_refused() {
+    for version in [2, VERSION + 1] {
+        let mut bytes = encoded();
+        bytes[4..6].copy_from_slice(&u16::to_le_bytes(version));
+        match savestate::decode(&bytes, ROM_HASH) {
+            Err(SaveStateError::UnsupportedVersion(found)) => assert_eq!(found, version),
+            other => panic!("version {}: {:?}", version, other),
+        }
+    }
+
+    // a header alone is enough to tell
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&2u16.to_le_bytes());
+    assert!(matches!(savestate::decode(&header, ROM_HASH), Err(SaveStateError::UnsupportedVersion(2))));
+}
+
+#[test]
+fn other_roms_are_refused() {
+    assert!(matches!(savestate::decode(&encoded(), ROM_HASH ^ 1), Err(SaveStateError::RomMismatch)));
+    assert_ne!(savestate::rom_hash(&[0x12, 0x00]), savestate::rom_hash(&[0x12, 0x02]));
+}
+
+#[test]
+fn memory_size_must_be_chip8_or_xochip() {
+    for size in [0u32, 1, CHIP8_RAM_SIZE_BYTES as u32 - 1, CHIP8_RAM_SIZE_BYTES as u32 * 2, u32::MAX] {
+        let mut bytes = encoded();
+        bytes[MEMORY_SIZE..MEMORY_SIZE + 4].copy_from_slice(&size.to_le_bytes());
+        assert!(matches!(savestate::decode(&bytes, ROM_HASH), Err(SaveStateError::Corrupt)), "memory size {}", size);
+    }
+}
+
+#[test]
+fn screen_size_must_fit_the_display() {
+    for (width, height) in [(0u16, 32u16), (64, 0), (129, 64), (128, 65), (u16::MAX, u16::MAX)] {
+        let mut bytes = encoded();
+        bytes[SCREEN_SIZE..SCREEN_SIZE + 2].copy_from_slice(&width.to_le_bytes());
+        bytes[SCREEN_SIZE + 2..SCREEN_SIZE + 4].copy_from_slice(&height.to_le_bytes());
+        assert!(matches!(savestate::decode(&bytes, ROM_HASH), Err(SaveStateError::Corrupt)), "screen {}x{}", width, height);
+    }
+
+    // a hires screen in a lores state leaves the pixels short
+    let mut bytes = encoded();
+    bytes[SCREEN_SIZE..SCREEN_SIZE + 2].copy_from_slice(&128u16.to_le_bytes());
+    bytes[SCREEN_SIZE + 2..SCREEN_SIZE + 4].copy_from_slice(&64u16.to_le_bytes());
+    assert!(matches!(savestate::decode(&bytes, ROM_HASH), Err(SaveStateError::Corrupt)));
+}
+
+#[test]
+fn truncated_or_padded_files_are_corrupt() {
+    let bytes = encoded();
+    for length in [MEMORY_SIZE, SCREEN_SIZE, bytes.len() - 1] {
+        assert!(matches!(savestate::decode(&bytes[..length], ROM_HASH), Err(SaveStateError::Corrupt)), "length {}", length);
+    }
+
+    let mut padded = bytes.clone();
+    padded.push(0);
+    assert!(matches!(savestate::decode(&padded, ROM_HASH), Err(SaveStateError::Corrupt)));
+}
@@ -0,0 +1,88 @@
+// Trace lines, the address filters and the line cap, and parsing them from the command line.
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use chip8_emulator_rust::{CHIP8_START_OF_PROGRAM, XOCHIP_PROGRAM_SIZE, processor::Processor, quirks::Quirks, trace::{self, Tracer}};
+
+// Lets the test read what the tracer wrote
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// v0 += 1 and loop, run cycles instructions and return the trace
+fn run(cycles: usize, configure: impl FnOnce(&mut Tracer)) -> Vec<String> {
+    let mut program = [0u8; XOCHIP_PROGRAM_SIZE];
+    program[..6].copy_from_slice(&[0x70, 0x01, 0x00, 0xE0, 0x12, 0x00]);
+    let mut processor = Processor::new(Quirks::modern());
+    processor.load(program, 6, CHIP8_START_OF_PROGRAM);
+
+    let output = Output::default();
+    let mut tracer = Tracer::new(Box::new(output.clone()));
+    configure(&mut tracer);
+    processor.set_tracer(Some(tracer));
+    for _ in 0..cycles {
+        processor.cycle([false; 16]).unwrap();
+    }
+    processor.set_tracer(None);
+
+    let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+    text.lines().map(String::from).collect()
+}
+
+#[test]
+fn one_fixed_width_line_per_instruction() {
+    let lines = run(4, |_| {});
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[3], "00000004 0200 7001   ADD V0, 0x01         01 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 0000 0  00 00");
+    assert!(lines.iter().all(|line| line.len() == lines[0].len()));
+}
+
+#[test]
+fn ranges_filter_by_pc() {
+    let lines = run(9, |tracer| {
+        tracer.add_range(0x202, 0x202);
+        tracer.add_range(0x204, 0x300);
+    });
+    let pcs: Vec<&str> = lines.iter().map(|line| &line[9..13]).collect();
+    assert_eq!(pcs, ["0202", "0204", "0202", "0204", "0202", "0204"]);
+    // the cycle count keeps counting the instructions that weren't traced
+    assert!(lines[5].starts_with("00000009 "));
+}
+
+#[test]
+fn max_lines_caps_the_trace() {
+    assert_eq!(run(9, |tracer| tracer.set_max_lines(Some(2))).len(), 2);
+    assert_eq!(run(9, |tracer| {
+        tracer.add_range(0x204, 0x204);
+        tracer.set_max_lines(Some(2));
+    }).len(), 2);
+}
+
+#[test]
+fn parse_range() {
+    assert_eq!(trace::parse_range("200-2ff"), Ok((0x200, 0x2FF)));
+    assert_eq!(trace::parse_range("0x200-0x200"), Ok((0x200, 0x200)));
+    assert_eq!(trace::parse_range("300-200").unwrap_err(), "start must not be past its end, got 300-200");
+    for text in ["200", "200-", "-200", "zz-300", "200-10000"] {
+        assert_eq!(trace::parse_range(text).unwrap_err(), format!("expects <start>-<end> in hex, got '{}'", text));
+    }
+}
+
+#[test]
+fn parse_max_lines() {
+    assert_eq!(trace::parse_max_lines("1"), Ok(1));
+    assert_eq!(trace::parse_max_lines("100000"), Ok(100_000));
+    assert!(trace::parse_max_lines("0").is_err());
+    assert!(trace::parse_max_lines("-1").is_err());
+    assert!(trace::parse_max_lines("ten").is_err());
+}
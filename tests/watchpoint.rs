@@ -0,0 +1,115 @@
+// Watchpoints around single instructions, driven the way the debugger drives them.
+use std::cell::RefCell;
+use std::rc::Rc;
+use chip8_emulator_rust::{CHIP8_START_OF_PROGRAM, XOCHIP_PROGRAM_SIZE, debugger::Command, memory::{Access, AccessKind}, processor::Processor, quirks::Quirks, watchpoint::{Hit, HitKind, Target, Trigger, Watchpoints}};
+
+fn processor(opcodes: &[u16]) -> Processor {
+    let mut program = [0u8; XOCHIP_PROGRAM_SIZE];
+    for (i, opcode) in opcodes.iter().enumerate() {
+        program[i * 2..i * 2 + 2].copy_from_slice(&opcode.to_be_bytes());
+    }
+
+    let mut processor = Processor::new(Quirks::modern());
+    processor.load(program, opcodes.len() * 2, CHIP8_START_OF_PROGRAM);
+    processor
+}
+
+fn step(watchpoints: &mut Watchpoints, processor: &mut Processor) -> Vec<Hit> {
+    watchpoints.before(processor);
+    processor.cycle([false; 16]).unwrap();
+    watchpoints.after(processor)
+}
+
+#[test]
+fn register_write_with_the_same_value_fires() {
+    // v1 := 1, v1 += v1 leaves vf at 0, v2 := 0
+    let mut processor = processor(&[0x6101, 0x8114, 0x6200]);
+    let mut watchpoints = Watchpoints::new();
+    let id = watchpoints.add(Target::Register(0xF));
+
+    assert!(step(&mut watchpoints, &mut processor).is_empty());
+    let hits = step(&mut watchpoints, &mut processor);
+    assert_eq!(hits, [Hit { id, pc: 0x202, target: Target::Register(0xF), kind: HitKind::Written { old: 0, new: 0 } }]);
+    assert_eq!(hits[0].to_string(), "watchpoint 1: VF written 00 -> 00 at 202");
+    assert!(step(&mut watchpoints, &mut processor).is_empty());
+}
+
+#[test]
+fn index_and_timer_writes_fire() {
+    // i := 0x300, v0 := 5, delay := v0, buzzer := v0
+    let mut processor = processor(&[0xA300, 0x6005, 0xF015, 0xF018]);
+    let mut watchpoints = Watchpoints::new();
+    watchpoints.add(Target::Index);
+    watchpoints.add(Target::DelayTimer);
+    watchpoints.add(Target::SoundTimer);
+
+    let targets: Vec<Vec<Target>> = (0..4).map(|_| step(&mut watchpoints, &mut processor).iter().map(|hit| hit.target).collect()).collect();
+    assert_eq!(targets, [vec![Target::Index], vec![], vec![Target::DelayTimer], vec![Target::SoundTimer]]);
+}
+
+#[test]
+fn timer_ticks_fire() {
+    let mut processor = processor(&[]);
+    processor.set_delay_timer(1);
+    let mut watchpoints = Watchpoints::new();
+    watchpoints.add(Target::DelayTimer);
+    watchpoints.add(Target::SoundTimer);
+
+    let hits = watchpoints.tick_timers(&mut processor);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].kind, HitKind::Ticked { old: 1, new: 0 });
+    assert_eq!(processor.delay_timer(), 0);
+    // a timer at 0 doesn't tick
+    assert!(watchpoints.tick_timers(&mut processor).is_empty());
+}
+
+#[test]
+fn memory_accesses_by_kind() {
+    // i := 0x300, v1 := 7, save v1, load v0
+    let mut processor = processor(&[0xA300, 0x6107, 0xF155, 0xF065]);
+    let mut watchpoints = Watchpoints::new();
+    watchpoints.add(Target::Memory { address: 0x301, length: 1, trigger: Trigger::Write });
+    watchpoints.add(Target::Memory { address: 0x300, length: 4, trigger: Trigger::Read });
+
+    step(&mut watchpoints, &mut processor);
+    step(&mut watchpoints, &mut processor);
+    let hits = step(&mut watchpoints, &mut processor);
+    assert_eq!(hits.iter().map(|hit| hit.kind).collect::<Vec<_>>(), [HitKind::Memory(Access { address: 0x301, kind: AccessKind::Write })]);
+    let hits = step(&mut watchpoints, &mut processor);
+    assert_eq!(hits.iter().map(|hit| hit.kind).collect::<Vec<_>>(), [HitKind::Memory(Access { address: 0x300, kind: AccessKind::Read })]);
+}
+
+#[test]
+fn callbacks_keep_running() {
+    let mut processor = processor(&[0x6001, 0x6002]);
+    let mut watchpoints = Watchpoints::new();
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let log = seen.clone();
+    watchpoints.add_with_callback(Target::Register(0), Box::new(move |hit| log.borrow_mut().push(hit.pc)));
+
+    assert!(step(&mut watchpoints, &mut processor).is_empty());
+    assert!(step(&mut watchpoints, &mut processor).is_empty());
+    assert_eq!(*seen.borrow(), [0x200, 0x202]);
+}
+
+#[test]
+fn huge_lengths_saturate() {
+    let target = Target::Memory { address: 0x300, length: usize::MAX, trigger: Trigger::ReadWrite };
+    assert_eq!(target.to_string(), format!("300-{:03X} (read/write)", usize::MAX));
+
+    // i := 0x300, load v0
+    let mut processor = processor(&[0xA300, 0xF065]);
+    let mut watchpoints = Watchpoints::new();
+    watchpoints.add(target);
+    step(&mut watchpoints, &mut processor);
+    assert_eq!(step(&mut watchpoints, &mut processor).len(), 1);
+}
+
+#[test]
+fn watch_command_rejects_ranges_past_memory() {
+    assert_eq!(Command::parse("watch 300 16 w"), Ok(Command::Watch(Some(Target::Memory { address: 0x300, length: 16, trigger: Trigger::Write }))));
+    assert_eq!(Command::parse("watch vf"), Ok(Command::Watch(Some(Target::Register(0xF)))));
+    assert!(Command::parse("watch FFFF 2").is_err());
+    assert!(Command::parse("watch 1 18446744073709551615").is_err());
+    assert!(Command::parse("watch pc").is_err());
+}